@@ -0,0 +1,152 @@
+#![allow(dead_code, unused)]
+#![feature(box_syntax)]
+
+use ::rustcraft::prelude::*;
+use server::*;
+use std::net::TcpListener;
+
+fn main() {
+
+    let config = ServerConfig::load("server.toml").unwrap_or_else(|e| panic!("Could not load config: {}", e));
+    let args = std::env::args().collect::<Vec<_>>();
+    let arg = |name: &str| args.iter()
+        .position(|a| a == name)
+        .and_then(|i| args.get(i + 1))
+        .cloned();
+
+    println!("Loading...");
+    let mut content = ContentBuilder::new();
+    content.load_mod(&mut content::base::BaseMod);
+
+    if let Some(path) = arg("--replay") {
+        replay(content, &path);
+        return
+    }
+
+    // the ids the world already uses, or none for a new world
+    let block_ids = save::LevelData::read(&save::world_dir(&config.world))
+        .map(|level| level.block_ids)
+        .unwrap_or_default();
+    let content: Arc<Content> = content.finish_with_ids(&block_ids)
+        .unwrap_or_else(|e| panic!("Could not load content: {}", e))
+        .into();
+
+    if !save::world_exists(&config.world) {
+        println!("Creating world {}", config.world);
+        let level = save::LevelData::new(&config.seed, &content);
+        save::create_world(&config.world, &level).unwrap();
+    }
+
+    let clients = listen(config.port, content.clone());
+
+    let mut server = ServerLoop::new(&content, &config.world);
+    server.tick_duration = config.tick_duration();
+    server.view_distance = config.view_distance;
+    server.max_chunks = config.max_chunks();
+    server.operators = config.operators.clone();
+    if let Some(path) = arg("--record") {
+        match Recorder::create(&path, &server) {
+            Ok(recorder) => {
+                println!("Recording to {}", path);
+                server.recorder = Some(recorder);
+            }
+            Err(e) => println!("Could not record to {}: {}", path, e),
+        }
+    }
+    let stdin = util::AsyncStdin::new();
+    let admin = match &config.rcon_password {
+        Some(password) if !password.is_empty() => match rcon::listen(config.rcon_port, password.clone()) {
+            Ok(admin) => {
+                println!("Admin console on port {}", config.rcon_port);
+                Some(admin)
+            }
+            Err(e) => {
+                println!("Could not open admin console on port {}: {}", config.rcon_port, e);
+                None
+            }
+        },
+        _ => None,
+    };
+
+    println!("Server started on port {}", config.port);
+    'run: while server.run_and_sleep() {
+        for (conn, name) in clients.try_iter() {
+            server.connect(box conn, &name);
+        }
+        for line in stdin.rx.try_iter() {
+            let line = line.trim();
+            if line == "stop" {
+                break 'run
+            }
+            server.command(line, None);
+        }
+        for (line, reply) in admin.iter().flat_map(|rx| rx.try_iter()) {
+            if line == "stop" {
+                let _ = reply.send("Stopping server".into());
+                break 'run
+            }
+            server.command(&line, Some(reply));
+        }
+    }
+
+    if server.recorder.is_some() {
+        server.stop_recording();
+    }
+    println!("Saving world...");
+    if let Err(e) = server.world.save_all() {
+        println!("Failed to save world: {}", e);
+    }
+    server.shutdown();
+    println!("Server stopped");
+
+}
+
+/// Play back the recording at `path` without waiting between ticks
+fn replay(content: ContentBuilder, path: &str) {
+    let (playback, header) = server::restore_replay(path, consts::REPLAY_WORLD)
+        .unwrap_or_else(|e| panic!("Could not open replay {}: {}", path, e));
+    let block_ids = save::LevelData::read(&save::world_dir(consts::REPLAY_WORLD))
+        .map(|level| level.block_ids)
+        .unwrap_or_default();
+    let content = content.finish_with_ids(&block_ids)
+        .unwrap_or_else(|e| panic!("Could not load content: {}", e));
+    let mut server = ServerLoop::open_replay(&content, playback, &header, consts::REPLAY_WORLD);
+    println!("Replaying {}...", path);
+    while server.replay_tick() {}
+    if let Err(e) = server.world.save_all() {
+        println!("Failed to save world: {}", e);
+    }
+    server.shutdown();
+    println!("Replayed world saved as {}", consts::REPLAY_WORLD);
+}
+
+/// Accept clients on a separate thread, handing over those that complete the handshake.
+/// Each handshake runs on a thread of its own, so a slow client does not hold up the others.
+fn listen(port: u16, content: Arc<Content>) -> mpsc::Receiver<(TcpConnection<ServerMsg, ClientMsg>, String)> {
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .unwrap_or_else(|e| panic!("Could not listen on port {}: {}", port, e));
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    println!("Failed to accept connection: {}", e);
+                    continue
+                }
+            };
+            let (tx, content) = (tx.clone(), content.clone());
+            std::thread::spawn(move || {
+                let addr = stream.peer_addr();
+                match server::accept(stream, &content) {
+                    Ok((conn, name)) => {
+                        println!("{:?} connected as {}", addr, name);
+                        let _ = tx.send((conn, name));
+                    }
+                    Err(e) => println!("Refused {:?}: {}", addr, e),
+                }
+            });
+        }
+    });
+    rx
+}
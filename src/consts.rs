@@ -3,10 +3,87 @@ use std::time::Duration;
 
 /// Seed used for world generation
 pub const DEBUG_SEED: &'static str = "a new seed!";
+/// Name of the world that is opened on start
+pub const DEBUG_WORLD: &'static str = "world";
+/// Directory containing all world directories
+pub const SAVES_DIR: &'static str = "saves";
+/// Directory world backups are written to
+pub const BACKUPS_DIR: &'static str = "backups";
+/// Ticks between autosaves
+pub const AUTOSAVE_INTERVAL_TICKS: u64 = 20 * 60 * 5; // 5 minutes
+/// Maximum number of chunks handed to the I/O workers per tick while autosaving
+pub const AUTOSAVE_CHUNKS_PER_TICK: usize = 16;
+/// Port the dedicated server listens on if `server.toml` does not say otherwise
+pub const DEFAULT_PORT: u16 = 25575;
+/// Port of the remote admin console if `server.toml` does not say otherwise
+pub const DEFAULT_RCON_PORT: u16 = 25576;
+/// Longest command line accepted by the remote admin console
+pub const MAX_RCON_LINE: usize = 1024;
+/// Time the remote admin console waits for the server to run a command
+pub const RCON_REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Radius in chunks kept loaded around a player
+pub const DEFAULT_VIEW_DISTANCE: i32 = 5;
+/// Radius in chunks around the spawn in which entities are always simulated
+pub const SPAWN_CHUNK_RADIUS: u8 = 1;
+/// Players the default limit of loaded chunks leaves room for, when `server.toml` does not set one
+pub const EXPECTED_PLAYERS: usize = 4;
+/// Most chunks unloaded per tick
+pub const UNLOAD_CHUNKS_PER_TICK: usize = 64;
+/// Chunks generated by the server per tick
+pub const LOAD_WORK_PER_TICK: usize = 16;
+/// Chunks sent to each client per tick
+pub const CHUNKS_SENT_PER_TICK: usize = 16;
+/// Largest network message accepted, in bytes
+pub const MAX_PACKET_SIZE: usize = 16 * 1024 * 1024;
+/// Time allowed for each step of the connection handshake
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Messages waiting to be written to a connection before the other side is considered too slow
+pub const SEND_QUEUE_LENGTH: usize = 4096;
+/// Time a connection may take to accept more written data before it is dropped
+pub const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest player name accepted by servers
+pub const MAX_NAME_LENGTH: usize = 16;
+/// Longest sign text accepted by servers, in characters
+pub const MAX_SIGN_TEXT_LENGTH: usize = 64;
+/// Distance from the eyes within which blocks can be broken or placed
+pub const REACH_DISTANCE: f32 = 5.;
+/// Half the diagonal of a block. Reach is measured to the block centre, which can be this
+/// much further away than the point the player aims at
+pub const BLOCK_HALF_DIAGONAL: f32 = 0.87;
+/// Longest frame simulated at once, in seconds
+pub const MAX_FRAME_DELTA: f32 = 0.1;
+/// Seconds of movement a client may send ahead of real time, to absorb latency spikes
+pub const MAX_INPUT_BACKLOG: f32 = 1.;
+/// Distance between the predicted and the server's player position above which the client rewinds
+pub const RECONCILE_DISTANCE: f32 = 0.01;
+/// Ticks a block of hardness 1 has to be dug before it breaks
+pub const BREAK_TICKS: u64 = 20;
+/// Ticks a dig may finish early on the server, to allow for latency
+pub const DIG_TOLERANCE_TICKS: u64 = 4;
+/// Name of the scratch world replays are played back in
+pub const REPLAY_WORLD: &'static str = "replay";
+/// Time a replay waits for a chunk it needs to be read from storage before giving up
+pub const REPLAY_READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// Time the output of a command stays on the client's screen
+pub const COMMAND_OUTPUT_DURATION: Duration = Duration::from_secs(5);
 /// Tick interval duration
 pub const TICK_DURATION: Duration = Duration::from_millis(50);
 /// Number of random ticks per chunk per game tick
 pub const RANDOM_TICK_SPEED: usize = 3;
+/// Ticks a furnace takes to smelt one item
+pub const SMELT_TICKS: u32 = 200;
+/// Ticks a furnace burns for on one flammable block
+pub const FUEL_BURN_TICKS: u32 = 300;
+/// Side length of a region file in chunks
+pub const REGION_SIZE: i32 = 8;
+/// Most region files kept in memory by a world's storage
+pub const MAX_OPEN_REGIONS: usize = 64;
+/// Number of threads generating terrain
+pub const TERRAIN_WORKER_THREADS: usize = 4;
+/// Number of threads meshing chunks for rendering
+pub const MESH_WORKER_THREADS: usize = 2;
+/// Maximum number of queued chunk I/O jobs
+pub const IO_QUEUE_SIZE: usize = 64;
 /// Sky minimum brightness
 pub const SKY_MIN_BRIGHTNESS: f32 = 0.0;
 /// Minimum block brightness
@@ -0,0 +1,41 @@
+#![allow(dead_code, unused)]
+#![deny(unconditional_recursion)]
+#![feature(box_patterns, box_syntax, generators, generator_trait, try_blocks, duration_constants, result_cloned)]
+
+#[macro_use]
+extern crate warn;
+
+pub mod util;
+#[cfg(feature = "client")]
+pub mod engine;
+pub mod coords;
+pub mod rustcraft;
+pub mod perlin;
+pub mod consts;
+pub mod server;
+pub use crate::builder::ContentBuilder;
+pub use crate::crafting::CraftingRegistry;
+pub use crate::util::BVH;
+pub use crate::content::*;
+pub use rustcraft::component::*;
+pub use rustcraft::*;
+pub use rustcraft::inventory;
+#[cfg(feature = "client")]
+pub use crate::client::*;
+
+/// Everything that needs a window, OpenGL or audio
+#[cfg(feature = "client")]
+mod client {
+    pub use crate::game_loop::GameLoop;
+    pub use crate::util::gen_full_block_vao;
+    pub use crate::lines::box_vao;
+    pub use crate::text::font::Font;
+    pub use crate::display::GLDisplay;
+    pub use crate::engine::program::*;
+    pub use crate::rustcraft::input::Input;
+    pub use crate::engine::texture::*;
+    pub use crate::engine::*;
+}
+
+pub mod prelude;
+use crate::prelude::*;
@@ -1,55 +1,69 @@
 #![allow(dead_code, unused)]
-#![deny(unconditional_recursion)]
-#![feature(box_patterns, box_syntax, generators, generator_trait, try_blocks, duration_constants, result_cloned)]
-
-#[macro_use]
-extern crate warn;
-
-pub mod util;
-pub mod engine;
-pub mod coords;
-pub mod rustcraft;
-pub mod perlin;
-pub mod consts;
-pub mod server;
-use crate::builder::ContentBuilder;
-use crate::game_loop::GameLoop;
-use crate::util::gen_full_block_vao;
-use crate::lines::box_vao;
-use crate::crafting::CraftingRegistry;
-use crate::text::font::Font;
-use crate::display::GLDisplay;
-use crate::util::BVH;
-use crate::content::*;
-use engine::program::*;
-use rustcraft::component::*;
-use crate::rustcraft::input::Input;
-use crate::engine::texture::*;
-use engine::*;
-use rustcraft::*;
-
-pub mod prelude;
-use crate::prelude::*;
+#![feature(box_syntax)]
+
+use ::rustcraft::prelude::*;
 
 fn main() {
 
     println!("Loading...");
     let mut data = init_data();
     let mut rdata = init_rdata(&data);
-    let idata = init_idata();
 
-    let (conn, server) = server(idata.content.clone());
+    let args = std::env::args().collect::<Vec<_>>();
+    let remote = args.iter()
+        .position(|a| a == "--connect")
+        .and_then(|i| args.get(i + 1));
+    let name = args.iter()
+        .position(|a| a == "--name")
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+        .unwrap_or_else(|| data.settings.name.clone());
+    if let Some(reason) = server::check_name(&name) {
+        panic!("Invalid player name: {}", reason)
+    }
+
+    let mut content = ContentBuilder::new();
+    content.load_mod(&mut content::base::BaseMod);
+    // blocks are numbered like the server or the saved world does
+    let (remote_conn, block_ids) = if let Some(addr) = remote {
+        println!("Connecting to {}...", addr);
+        let (conn, block_ids) = server::connect(addr.as_str(), &name, &content.mods).unwrap_or_else(|e| {
+            panic!("Could not connect to {}: {}", addr, e)
+        });
+        (Some(conn), block_ids)
+    } else {
+        let block_ids = save::LevelData::read(&save::world_dir(consts::DEBUG_WORLD))
+            .map(|level| level.block_ids)
+            .unwrap_or_default();
+        (None, block_ids)
+    };
+    let content = content.finish_with_ids(&block_ids)
+        .unwrap_or_else(|e| panic!("Could not load content: {}", e));
+    let idata = init_idata(content);
+
+    let (conn, server): (Box<dyn Connection<ClientMsg, ServerMsg>>, _) = if let Some(conn) = remote_conn {
+        (box conn, None)
+    } else {
+        if !save::world_exists(consts::DEBUG_WORLD) {
+            let level = save::LevelData::new(consts::DEBUG_SEED, &idata.content);
+            save::create_world(consts::DEBUG_WORLD, &level).unwrap();
+        }
+        let (conn, server) = server(idata.content.clone(), name);
+        (box conn, Some(server))
+    };
     let mut game_loop = GameLoop::new(conn, &mut data, &mut rdata, &idata);
     
     println!("Client started");
     game_loop.run_loop();
     println!("Client stopped");
 
-    server.stop();
+    if let Some(server) = server {
+        server.stop();
+    }
 }
 
 
-use mpsc::{Sender,Receiver,channel};
+use mpsc::{Sender,channel};
 use server::*;
 struct Server {
     kill: Sender<()>,
@@ -61,22 +75,27 @@ impl Server {
         self.thread.join();
     }
 }
-fn server(content: Arc<Content>) -> ((Sender<ClientMsg>, Receiver<ServerMsg>), Server) {
+fn server(content: Arc<Content>, name: String) -> (ChannelConnection<ClientMsg, ServerMsg>, Server) {
     
-    let (stx,rx) = channel();
-    let (tx,srx) = channel();
+    let (conn, sconn) = channel_pair();
     let (kill,krx) = channel();
 
     let thread = std::thread::spawn(move || {
 
         println!("Server start");
-        let mut server = ServerLoop::new((stx, srx), &content);
+        let mut server = ServerLoop::new(&content, consts::DEBUG_WORLD);
+        server.connect(box sconn, &name);
         while server.run_and_sleep() && !krx.try_recv().is_ok() {} 
+        println!("Saving world...");
+        if let Err(e) = server.world.save_all() {
+            println!("Failed to save world: {}", e);
+        }
+        server.shutdown();
         println!("Server stop");
 
     });
 
-    ((tx,rx), Server {
+    (conn, Server {
         kill,
         thread,
     })
@@ -110,7 +129,7 @@ fn init_data() -> data::Data {
     }
 }
 
-fn init_idata() -> data::IData {
+fn init_idata(content: Content) -> data::IData {
     let atlas = Arc::new(TextureAtlas::new(
         Texture::from_path("assets/atlas.png"),
         6
@@ -120,9 +139,7 @@ fn init_idata() -> data::IData {
         4
     ).into();
 
-    let mut content = ContentBuilder::new();
-    content.load_mod(&mut content::base::BaseMod);
-    let content: Arc<_> = content.finish().into();
+    let content: Arc<_> = content.into();
 
     let font = Font::from_font_files("assets/font.png", "assets/font.fnt").into();
     let line_box = lines::box_vao().into();
@@ -7,7 +7,9 @@ pub use game::settings::Settings;
 pub use util::ArcStr;
 #[macro_use]
 pub use util;
+#[cfg(feature = "client")]
 pub use engine;
+#[cfg(feature = "client")]
 pub use engine::audio::{AudioSys, self};
 pub use rustcraft as game;
 pub use game::world::{self, *};
@@ -15,11 +17,15 @@ pub use game::item::*;
 pub use crate::*;
 pub use game::component;
 pub use cgmath::*;
-pub use crate::registry::ItemRegistry;
+pub use crate::registry::{ItemRegistry, BlockRegistry, BlockId, BlockIdMap};
 pub use crate::coords::*;
 pub use std::collections::{HashSet, HashMap, VecDeque, BinaryHeap};
+#[cfg(feature = "client")]
 pub use crate::vao::VAO;
-pub use game::chunk::{self, chunk::*, meshing};
+pub use game::chunk::{self, chunk::*};
+pub use game::chunk::block_entity::{BlockEntity, BlockEntityKind};
+#[cfg(feature = "client")]
+pub use game::chunk::meshing;
 pub use std::sync::{mpsc, Arc};
 pub use std::time::{Duration, Instant};
 pub type V3f = Vector3<f32>;
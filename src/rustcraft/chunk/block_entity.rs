@@ -0,0 +1,142 @@
+use crate::prelude::*;
+
+/// Kind of block entity a block carries, declared in `items.toml` as `block_entity = { inventory = 27 }`
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockEntityKind {
+    /// Item slots, this many of them
+    Inventory(usize),
+    /// Smelts its input using flammable blocks as fuel
+    Furnace,
+    /// A line of text
+    Sign,
+}
+
+impl BlockEntityKind {
+    /// Data of a freshly placed block of this kind
+    pub fn create(&self) -> BlockEntity {
+        match self {
+            Self::Inventory(slots) => BlockEntity::Inventory(vec![None; *slots]),
+            Self::Furnace => BlockEntity::Furnace {
+                slots: vec![None; 3],
+                burn_ticks: 0,
+                progress: 0,
+            },
+            Self::Sign => BlockEntity::Sign(String::new()),
+        }
+    }
+}
+
+/// Data kept for a single block beyond its state, like the contents of a chest
+#[derive(Clone, Debug)]
+pub enum BlockEntity {
+    Inventory(Vec<Option<ItemStack>>),
+    Furnace {
+        /// Input, fuel and output
+        slots: Vec<Option<ItemStack>>,
+        /// Ticks until the current fuel is used up
+        burn_ticks: u32,
+        /// Ticks the current input has been smelting for
+        progress: u32,
+    },
+    Sign(String),
+}
+
+impl BlockEntity {
+
+    pub const FURNACE_INPUT: usize = 0;
+    pub const FURNACE_FUEL: usize = 1;
+    pub const FURNACE_OUTPUT: usize = 2;
+
+    /// Item slots, if it has any
+    pub fn slots(&self) -> Option<&Vec<Option<ItemStack>>> {
+        match self {
+            Self::Inventory(slots) | Self::Furnace { slots, .. } => Some(slots),
+            Self::Sign(_) => None,
+        }
+    }
+
+    pub fn slots_mut(&mut self) -> Option<&mut Vec<Option<ItemStack>>> {
+        match self {
+            Self::Inventory(slots) | Self::Furnace { slots, .. } => Some(slots),
+            Self::Sign(_) => None,
+        }
+    }
+
+    /// Advance by one game tick, returns whether anything changed
+    pub fn tick(&mut self, content: &Content) -> bool {
+        let (slots, burn_ticks, progress) = match self {
+            Self::Furnace { slots, burn_ticks, progress } => (slots, burn_ticks, progress),
+            _ => return false
+        };
+        let output = slots[Self::FURNACE_INPUT].as_ref()
+            .and_then(|input| content.crafting.smelt(&input.item))
+            .filter(|out| match &slots[Self::FURNACE_OUTPUT] {
+                Some(stack) => stack.item == out.item && stack.count + out.count <= 64,
+                None => true,
+            })
+            .cloned();
+        let mut changed = false;
+        if *burn_ticks == 0 && output.is_some() {
+            let is_fuel = slots[Self::FURNACE_FUEL].as_ref()
+                .and_then(|fuel| fuel.item.as_block())
+                .map_or(false, |b| b.flammable);
+            if is_fuel {
+                ItemStack::deduct(&mut slots[Self::FURNACE_FUEL], 1);
+                *burn_ticks = consts::FUEL_BURN_TICKS;
+                changed = true;
+            }
+        }
+        match output {
+            Some(output) if *burn_ticks > 0 => {
+                *progress += 1;
+                if *progress >= consts::SMELT_TICKS {
+                    *progress = 0;
+                    ItemStack::deduct(&mut slots[Self::FURNACE_INPUT], 1);
+                    ItemStack::merge(&mut Some(output), &mut slots[Self::FURNACE_OUTPUT]);
+                }
+                changed = true;
+            }
+            // smelting starts over once there is something to smelt again
+            _ if *progress > 0 => {
+                *progress = 0;
+                changed = true;
+            }
+            _ => {}
+        }
+        if *burn_ticks > 0 {
+            *burn_ticks -= 1;
+            changed = true;
+        }
+        changed
+    }
+
+    /// Tick the block entities of ticking chunks
+    pub fn system_tick(data: &mut WorldData, content: &Content) {
+        let tickets = &data.tickets;
+        for (cp, c) in data.blocks.chunks.iter_mut() {
+            if c.chunk.block_entities.is_empty() || tickets.status_at(*cp) < ChunkStatus::Ticking {
+                continue
+            }
+            let mut changed = false;
+            for ent in c.chunk.block_entities.values_mut() {
+                changed |= ent.tick(content);
+            }
+            if changed {
+                data.blocks.unsaved_chunks.insert(*cp);
+            }
+        }
+    }
+
+    /// Spill the items of the block entities whose blocks were removed
+    pub fn system_spill_dropped(data: &mut WorldData) {
+        for (pos, ent) in std::mem::take(&mut data.blocks.dropped_block_entities) {
+            if let Some(slots) = ent.slots() {
+                for stack in slots.iter().flatten() {
+                    component::ItemCmp::spawn(data, pos.align_center(), stack.clone());
+                }
+            }
+        }
+    }
+
+}
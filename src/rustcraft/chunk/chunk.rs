@@ -1,8 +1,8 @@
 
-use crate::world::VoxelData;
 use crate::prelude::*;
 use crate::util::AABB;
 use super::lighting::*;
+use super::palette::BlockStorage;
 
 /// Signifies the current state of the chunk
 #[derive(PartialOrd,PartialEq,Eq,Ord,Clone,Copy,Debug,serde::Serialize,serde::Deserialize)]
@@ -17,38 +17,83 @@ pub enum ChunkState {
     Rendered
 }
 
-#[derive(Default, Clone, Copy)]
+impl ChunkState {
+    /// All states in order, a state is stored as its index in this list
+    pub const ALL: [ChunkState; 4] = [Self::Empty, Self::Filled, Self::Detailed, Self::Rendered];
+
+    /// The state generation brings a chunk in this state to next
+    pub fn next(self) -> Option<Self> {
+        Self::ALL.get(self as usize + 1).copied()
+    }
+}
+
+/// What is simulated in a chunk, decided by its load level
+#[derive(PartialOrd,PartialEq,Eq,Ord,Clone,Copy,Debug)]
+pub enum ChunkStatus {
+    /// Not needed, can be unloaded
+    Unloaded,
+    /// Kept generated as a border for the chunks around, nothing happens in it
+    Terrain,
+    /// Block updates and random ticks run
+    Ticking,
+    /// Entities are simulated as well
+    EntityTicking,
+}
+
+/// Load level of a chunk. A load source of level `n` gives the chunks `d` chunks away level `n - d`.
+/// Each level needs the chunks around to be one generation stage behind, so there are two border levels.
+#[derive(Default, Clone, Copy, Debug)]
 pub struct ChunkLoadLevel {
+    /// Level coming from the neighbours
     pub spread: u8,
+    /// Level of the strongest source in this chunk
     pub source: u8,
 }
 
-fn add_src<'cnt>(lvl: u8, pos: ChunkPos, w: &mut VoxelData<'cnt>, air: &'cnt BlockData) {
-    let mut queue = VecDeque::new();
-    if let Some(c) = w.chunk_at_mut(pos) {
-        c.load_level.source = lvl;
-        queue.push_back(pos);
+impl ChunkLoadLevel {
+    pub const TERRAIN: u8 = 1;
+    pub const DETAILED: u8 = 2;
+    pub const TICKING: u8 = 3;
+    pub const ENTITY_TICKING: u8 = 4;
+
+    pub fn level(&self) -> u8 {
+        self.spread.max(self.source)
+    }
+
+    pub fn status(&self) -> ChunkStatus {
+        match self.level() {
+            0 => ChunkStatus::Unloaded,
+            Self::TERRAIN | Self::DETAILED => ChunkStatus::Terrain,
+            Self::TICKING => ChunkStatus::Ticking,
+            _ => ChunkStatus::EntityTicking,
+        }
     }
-    while let Some(p) = queue.pop_front() {
-        
+
+    /// State the chunk has to be generated up to
+    pub fn target_state(&self) -> ChunkState {
+        match self.level() {
+            0 => ChunkState::Empty,
+            Self::TERRAIN => ChunkState::Filled,
+            Self::DETAILED => ChunkState::Detailed,
+            _ => ChunkState::Rendered,
+        }
     }
 }
 
-pub type BlocksData<'cnt> = Vec<Vec<Vec<&'cnt BlockData>>>;
 pub type LightData = [[[Light; 16]; 16]; 16];
 
-pub struct Chunk<'cnt> {
-    pub load_level: ChunkLoadLevel,
+pub struct Chunk {
     pub chunk_state: ChunkState,
     pub needs_refresh: bool,
     pub pos: ChunkPos,
-    pub data: BlocksData<'cnt>,
+    pub data: BlockStorage,
     pub light: LightData,
     pub light_updates: LightUpdates,
-    pub mesh: Option<(VAO, VAO)>,
+    /// Data of the blocks that carry more than their state, see `BlockData::block_entity`
+    pub block_entities: HashMap<BlockPos, BlockEntity>,
 }
 
-impl<'cnt> std::fmt::Debug for Chunk<'cnt> {
+impl std::fmt::Debug for Chunk {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         f.debug_struct("Chunk")
             .field("pos", &self.pos)
@@ -57,19 +102,19 @@ impl<'cnt> std::fmt::Debug for Chunk<'cnt> {
     }
 }
 
-impl<'cnt> Chunk<'cnt> {
+impl Chunk {
 
-    pub fn new(pos: ChunkPos, air: &'cnt BlockData) -> Self {
-        let data = vec![vec![vec![air;16];16];16];
+    pub fn new(pos: ChunkPos, air: BlockId) -> Self {
+        let data = BlockStorage::filled(air);
         let light = [[[Light::default(); 16]; 16]; 16];
         Self {
             light_updates: LightUpdates::default(),
             chunk_state: ChunkState::Empty,
-            data, mesh: None,
+            data,
             pos,
             needs_refresh: false,
             light,
-            load_level: ChunkLoadLevel::default(),
+            block_entities: HashMap::new(),
         }
     }
 
@@ -84,7 +129,11 @@ impl<'cnt> Chunk<'cnt> {
         self.pos.map(|x| (x * 16 + 8) as f32)
     }
 
-    pub fn block_at(&self, pos: &impl Coord) -> &'cnt BlockData {&self[*pos]}
+    /// Numeric id of the block state at `pos`, see `BlockRegistry::by_id`
+    pub fn block_at(&self, pos: &impl Coord) -> BlockId {
+        let (x,y,z) = pos.as_sub().into();
+        self.data.get(x, y, z)
+    }
 
     pub fn light_at_mut(&mut self, pos: &impl Coord) -> &mut Light {
         let (x,y,z) = pos.as_sub().into();
@@ -95,22 +144,21 @@ impl<'cnt> Chunk<'cnt> {
         &self.light[x][y][z]
     }
 
-    pub fn light_update(&mut self, pos: &impl Coord) {
+    /// Update the block light at `pos` to `light`, emitted by the block now there
+    pub fn light_update(&mut self, pos: &impl Coord, light: u8) {
         let sc = pos.as_sub();
         let old_light = &mut self.light[sc.x][sc.y][sc.z];
-        let block = &mut self.data[sc.x][sc.y][sc.z];
-        if old_light.block() != block.light {
-            self.light_updates.reg_block(&pos.as_block(), old_light.block(), block.light);
-            self.light[sc.x][sc.y][sc.z].set_block(block.light);
+        if old_light.block() != light {
+            self.light_updates.reg_block(&pos.as_block(), old_light.block(), light);
+            self.light[sc.x][sc.y][sc.z].set_block(light);
             self.needs_refresh = true;
         }
     }
 
-    pub fn set_at(&mut self, pos: &impl Coord, block: &'cnt BlockData) -> bool {
-        let b = &mut self[*pos];
-        if !std::ptr::eq(*b, block) {
-            *b = block;
-            self.light_update(pos);
+    pub fn set_at(&mut self, pos: &impl Coord, block: &BlockData) -> bool {
+        let (x,y,z) = pos.as_sub().into();
+        if self.data.set(x, y, z, block.num_id) {
+            self.light_update(pos, block.light);
             self.needs_refresh = true;
             true
         } else {
@@ -118,45 +166,10 @@ impl<'cnt> Chunk<'cnt> {
         }
     }
 
-    pub fn gen_terrain(&mut self, noise: &dyn TerrainGenerator, reg: &'cnt Content) {
-        noise.gen_terrain(self, reg);
-        self.needs_refresh = true;
-        self.chunk_state = ChunkState::Filled;
-    }
-
     pub fn renderable(&self) -> bool {
         self.chunk_state == ChunkState::Rendered
     }
 
     pub fn aabb(&self) -> AABB { AABB::from_corner(&self.pos.map(|x| x as f32 * 16.), 16.) }
 
-    pub fn bind_and_draw(&self) {
-        if let Some(mesh) = &self.mesh {
-            mesh.0.bind();
-            mesh.0.draw();
-        }
-    }
-
-    pub fn bind_and_draw_second_pass(&self) {
-        if let Some(mesh) = &self.mesh {
-            mesh.1.bind();
-            mesh.1.draw();
-        }
-    }
-
-}
-
-impl<'cnt, C: Coord> std::ops::Index<C> for Chunk<'cnt> {
-    type Output = &'cnt BlockData;
-    fn index(&self, pos: C) -> &Self::Output {
-        let (x,y,z) = pos.as_sub().into();
-        &self.data[x][y][z]
-    }
-}
-
-impl<'cnt, C: Coord> std::ops::IndexMut<C> for Chunk<'cnt> {
-    fn index_mut(&mut self, pos: C) -> &mut Self::Output {
-        let (x,y,z) = pos.as_sub().into();
-        &mut self.data[x][y][z]
-    }
 }
@@ -1,49 +1,190 @@
-
 use crate::prelude::*;
 
+/// Rank of bringing a chunk with load `level` to `state`, lower ranks are worked on first.
+/// Higher levels are nearer to a load source, and every stage comes two ranks after the previous one,
+/// so the chunks around that a stage needs, which are at most one level lower, have been worked on before it.
+fn rank(level: u8, state: ChunkState) -> usize {
+    (u8::MAX - level) as usize + 2 * state as usize
+}
+
+/// Brings chunks to the state their load level asks for, one stage at a time and nearest to the load sources first
+#[derive(Default)]
 pub struct ChunkGenerator {
-    srcs: HashSet<ChunkPos>,
-    deque: MultiDeque<(ChunkPos,usize)>,
-    done: bool,
-    last_rank: usize,
+    deque: MultiDeque<ChunkPos>,
+    /// Chunks that had to wait for storage or the chunks around, tried again on the next step
+    blocked: Vec<ChunkPos>,
+    /// Set when load levels changed, the queue is rebuilt from them on the next step
+    dirty: bool,
+    /// Whether anything was generated since the queue last ran empty
+    working: bool,
 }
 
 impl ChunkGenerator {
-    pub fn step_n(&mut self, mut n: usize) {
+
+    /// Rebuild the queue on the next step, as the load levels changed
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn done(&self) -> bool {
+        self.deque.empty() && self.blocked.is_empty() && !self.dirty
+    }
+
+    /// Do up to `n` generation stages in `world`. Chunks that are not loaded yet wait while `max_chunks` are.
+    pub fn step_n<'cnt>(&mut self, world: &mut WorldData<'cnt>, reg: &'cnt Content, mut n: usize, max_chunks: usize) {
+        if self.dirty {
+            self.rebuild(world);
+        } else {
+            for p in std::mem::take(&mut self.blocked) {
+                let level = world.tickets.level_at(p);
+                if let Some(next) = Self::get_state(world, p).next() {
+                    self.deque.push(rank(level, next), p);
+                }
+            }
+        }
+
+        // keep the I/O workers busy with the chunks coming up
+        let upcoming = self.deque.iter()
+            .filter(|p| !world.blocks.chunks.contains_key(p))
+            .take(consts::IO_QUEUE_SIZE)
+            .copied()
+            .collect::<Vec<_>>();
+        for p in upcoming {
+            world.request_chunk(p);
+        }
+
         while n > 0 {
-            if self.done {return};
-            let rank = self.deque.get_rank();
-            let p = self.deque.poll();
-            if p.is_none() {
-                self.done = true;
-                return;
+            let p = match self.deque.poll() {
+                Some(p) => p,
+                None => break
+            };
+            let level = world.tickets.load_level(p);
+            let state = Self::get_state(world, p);
+            if state >= level.target_state() {
+                continue
             }
-            self.last_rank = rank;
-            let (p, spread) = p.unwrap();
-            let cur_state = self.get_state(p);
-            let too_high = cur_state >= rank;
-            if !too_high {
-                self.set_state(p, rank);
+            let next = state.next().unwrap();
+            if next == ChunkState::Filled && world.blocks.chunks.len() >= max_chunks {
+                // no room, unloading makes some once load sources move
+                self.blocked.push(p);
+                continue
+            }
+            if !Self::set_state(world, reg, p, next) {
+                self.blocked.push(p);
+                continue
+            }
+            self.working = true;
+            n -= 1;
+            if next < level.target_state() {
+                self.deque.push(rank(level.level(), next.next().unwrap()), p);
+            }
+        }
+
+        if self.working && self.done() {
+            println!("Done loading");
+            self.working = false;
+        }
+    }
+
+    /// Queue every chunk that is behind the state its level asks for
+    fn rebuild(&mut self, world: &mut WorldData) {
+        self.deque = MultiDeque::default();
+        self.blocked.clear();
+        self.dirty = false;
+        let mut todo = world.tickets.levels().iter()
+            .filter_map(|(p, level)| {
+                let state = Self::get_state(world, *p);
+                if state < level.target_state() {
+                    Some((rank(level.level(), state.next()?), p.as_tuple()))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        // the order chunks are detailed in changes where trees end up, it has to be the same in replays
+        todo.sort();
+        for (rank, p) in todo {
+            self.deque.push(rank, p.into());
+        }
+    }
+
+    fn get_state(world: &WorldData, p: ChunkPos) -> ChunkState {
+        world.blocks.chunks.get(&p).map_or(ChunkState::Empty, |c| c.chunk.chunk_state)
+    }
+
+    /// Bring the chunk at `p` to `state`, which is the one after its current state.
+    /// Returns false if it has to wait for storage or the chunks around it.
+    fn set_state<'cnt>(world: &mut WorldData<'cnt>, reg: &'cnt Content, p: ChunkPos, state: ChunkState) -> bool {
+        match state {
+            ChunkState::Empty => true,
+            ChunkState::Filled => {
+                let saved = match world.take_chunk(p) {
+                    Some(saved) => saved,
+                    // still being read or generated
+                    None => return false
+                };
+                let mut chunk = match Chunk::from_saved(&saved, reg) {
+                    Ok(chunk) => Box::new(chunk),
+                    Err(e) => {
+                        println!("Failed to load chunk {:?}, generating it again: {}", p, e);
+                        world.regenerate_chunk(p);
+                        return false
+                    }
+                };
+                if let Err(e) = crate::save::load_entities(&mut world.entities, &saved.entities, &reg.items) {
+                    println!("Failed to load entities of chunk {:?}: {}", p, e);
+                }
+                if !saved.entities.is_empty() {
+                    world.entity_chunks.insert(p);
+                }
+                // rendering needs the chunks around, which may not be loaded yet
+                chunk.chunk_state = chunk.chunk_state.min(ChunkState::Detailed);
+                world.blocks.insert_chunk(chunk);
+                true
             }
-            if spread > 1 {
-                for n in self.around(p) {
-                    self.deque.push(rank, (n, spread-1));
+            ChunkState::Detailed => {
+                // trees reach into the chunks around
+                if !Self::around_at_least(world, p, ChunkState::Filled) {
+                    return false
                 }
+                crate::world::gen_detail(p, world, reg);
+                world.blocks.unsaved_chunks.insert(p);
+                true
             }
-            if !too_high {
-                n -= 1;
+            ChunkState::Rendered => {
+                // light and meshes depend on the blocks around, which detailing may still change
+                if !Self::around_at_least(world, p, ChunkState::Detailed) {
+                    return false
+                }
+                // the mesh itself is made by the client's `ChunkRenderer`
+                let c = &mut world.blocks.chunks.get_mut(&p).unwrap().chunk;
+                c.needs_refresh = true;
+                c.chunk_state = ChunkState::Rendered;
+                true
             }
         }
     }
-    fn around(&self, p: ChunkPos) -> Vec<ChunkPos> {unimplemented!()}
-    fn get_state(&self, p: ChunkPos) -> usize {0}
-    fn set_state(&self, p: ChunkPos, n: usize) {}
+
+    /// Whether all 26 chunks around `p` are loaded and at least in `state`
+    fn around_at_least(world: &WorldData, p: ChunkPos, state: ChunkState) -> bool {
+        itertools::iproduct!(-1..=1, -1..=1, -1..=1)
+            .filter(|d| *d != (0, 0, 0))
+            .all(|(x, y, z)| Self::get_state(world, (p.x + x, p.y + y, p.z + z).into()) >= state)
+    }
+
 }
 
+/// Queues of elements by rank, the lowest rank is served first
 struct MultiDeque<E> {
     map: std::collections::BTreeMap<usize, VecDeque<E>>,
 }
 
+impl<E> Default for MultiDeque<E> {
+    fn default() -> Self {
+        Self { map: Default::default() }
+    }
+}
+
 impl<E> MultiDeque<E> {
     pub fn push(&mut self, rank: usize, e: E) {
         if let Some(deque) = self.map.get_mut(&rank) {
@@ -65,4 +206,8 @@ impl<E> MultiDeque<E> {
         res
     }
     pub fn empty(&self) -> bool {self.map.len() == 0}
-}
\ No newline at end of file
+    /// Elements in the order they would be polled
+    pub fn iter(&self) -> impl Iterator<Item = &E> {
+        self.map.values().flatten()
+    }
+}
@@ -26,6 +26,14 @@ impl LightUpdates {
 #[derive(Clone, Copy, Default)]
 pub struct Light(u8);
 impl Light {
+    #[inline(always)]
+    pub fn from_raw(val: u8) -> Self {
+        Self(val)
+    }
+    #[inline(always)]
+    pub fn raw(&self) -> u8 {
+        self.0
+    }
     #[inline(always)]
     pub fn block(&self) -> u8 {
         self.0 & 0xf
@@ -1,8 +1,21 @@
 
+use crate::chunk::Light;
+use crate::worker::WorkerPool;
 use crate::prelude::*;
 
+/// Vertices, texture coordinates and light of a mesh, as uploaded by `VAO::update_lit`
+pub type MeshData = (Vec<f32>, Vec<f32>, Vec<f32>);
+
+/// Meshes of the first and second render pass of a chunk, made from the snapshot with the given version
+pub type MeshedChunk = (ChunkPos, u64, (MeshData, MeshData));
+
 pub struct ChunkRenderer {
-    pub program: Program
+    pub program: Program,
+    pub meshes: HashMap<ChunkPos, (VAO, VAO)>,
+    workers: WorkerPool<(u64, ChunkSnapshot), MeshedChunk>,
+    /// Version of the latest snapshot sent to the workers for each chunk, older meshes are dropped
+    pending: HashMap<ChunkPos, u64>,
+    version: u64,
 }
 
 impl ChunkRenderer {
@@ -12,8 +25,15 @@ impl ChunkRenderer {
             include_str!("frag.glsl"),
             vec!["project","view","transform","globLight","fogColor"]
         );
+        let workers = WorkerPool::new(consts::MESH_WORKER_THREADS, |(version, snapshot): (u64, ChunkSnapshot)| {
+            (snapshot.pos, version, make_mesh(&snapshot))
+        });
         ChunkRenderer {
-            program
+            program,
+            meshes: HashMap::new(),
+            workers,
+            pending: HashMap::new(),
+            version: 0,
         }
     }
     pub fn load_fog_color(&self, color: &Vector3<f32>) {
@@ -28,21 +48,67 @@ impl ChunkRenderer {
     pub fn load_view(&self, mat: &Matrix4<f32>) {
         self.program.load_mat4(1, mat);
     }
-    pub fn render(&self, w: &WorldData) {
+    /// Send chunks that became renderable or changed to the mesh workers, upload the meshes they finished
+    /// and drop the meshes of chunks that are gone or no longer renderable
+    pub fn update(&mut self, w: &mut VoxelData, atlas: &TextureAtlas) {
+        let changed = w.refresh_light();
+        let renderable = |p: &ChunkPos| w.chunks.get(p).map_or(false, |c| c.chunk.renderable());
+        self.meshes.retain(|p, _| renderable(p));
+        self.pending.retain(|p, _| renderable(p));
+        let to_mesh = w.chunks.iter()
+            .filter(|(p, c)| c.chunk.renderable() && (
+                c.chunk.needs_refresh
+                    || changed.contains(p)
+                    || !(self.meshes.contains_key(p) || self.pending.contains_key(p))
+            ))
+            .map(|(p, _)| *p)
+            .collect::<Vec<_>>();
+        for p in to_mesh {
+            let snapshot = match ChunkSnapshot::take(p, w, atlas) {
+                Some(snapshot) => snapshot,
+                // the border is not loaded, try again next frame
+                None => continue
+            };
+            self.version += 1;
+            self.pending.insert(p, self.version);
+            self.workers.send((self.version, snapshot));
+            w.chunks.get_mut(&p).unwrap().chunk.needs_refresh = false;
+        }
+
+        for (p, version, (m1, m2)) in self.workers.iter_done() {
+            // a newer snapshot is on its way, or the chunk is gone
+            if self.pending.get(&p) != Some(&version) {
+                continue
+            }
+            self.pending.remove(&p);
+            if let Some(mesh) = self.meshes.get_mut(&p) {
+                mesh.0.update_lit(&m1.0, &m1.1, &m1.2);
+                mesh.1.update_lit(&m2.0, &m2.1, &m2.2);
+            } else {
+                self.meshes.insert(p, (
+                    VAO::textured_lit(&m1.0, &m1.1, &m1.2),
+                    VAO::textured_lit(&m2.0, &m2.1, &m2.2)
+                ));
+            }
+        }
+    }
+    pub fn render(&self) {
         self.program.enable();
         // First pass
-        for chunk in w.blocks.chunks.values().filter(|c| c.chunk.renderable()).map(|c| &c.chunk) {
+        for (pos, mesh) in &self.meshes {
             self.program.load_mat4(2, &Matrix4::from_translation(
-                chunk.pos.as_world().0
+                pos.as_world().0
             ));
-            chunk.bind_and_draw();
+            mesh.0.bind();
+            mesh.0.draw();
         }
         // Second pass
-        for chunk in w.blocks.chunks.values().filter(|c| c.chunk.renderable()).map(|c| &c.chunk) {
+        for (pos, mesh) in &self.meshes {
             self.program.load_mat4(2, &Matrix4::from_translation(
-                chunk.pos.as_world().0
+                pos.as_world().0
             ));
-            chunk.bind_and_draw_second_pass();
+            mesh.1.bind();
+            mesh.1.draw();
         }
     }
 }
@@ -166,11 +232,97 @@ pub fn cube_mesh() -> VAO {
 
 }
 
-pub fn make_mesh(pos: ChunkPos, w: &VoxelData, reg: &ItemRegistry, atlas: &TextureAtlas) -> ((Vec<f32>, Vec<f32>, Vec<f32>), (Vec<f32>, Vec<f32>, Vec<f32>)) {
+/// How a block is meshed, independent of the loaded content
+struct BlockLook {
+    transparent: bool,
+    semi_transparent: bool,
+    group_transparent: bool,
+    no_render: bool,
+    /// Atlas coordinates of the texture on each face, indexed by `Face`
+    uvs: [(f32, f32); 6],
+}
 
-    let now = std::time::Instant::now();
+/// Side length of a snapshot, a chunk with a one block border
+const SNAPSHOT_SIZE: i32 = 18;
+
+/// Blocks and light of a chunk and the one block border around it,
+/// everything `make_mesh` needs so it can run away from the world
+pub struct ChunkSnapshot {
+    pub pos: ChunkPos,
+    looks: Vec<BlockLook>,
+    /// Indices into `looks` in x-y-z order, starting at the border corner
+    blocks: Vec<u16>,
+    light: Vec<Light>,
+    uv_dif: f32,
+}
 
-    let air = reg.get("air");
+impl ChunkSnapshot {
+
+    /// Copy the chunk at `pos` and its border out of `w`, `None` if a chunk around it is not loaded
+    pub fn take(pos: ChunkPos, w: &VoxelData, atlas: &TextureAtlas) -> Option<Self> {
+        let (bx,by,bz) = pos.as_block().as_tuple();
+        let size = SNAPSHOT_SIZE as usize;
+        let mut palette: Vec<BlockId> = vec![];
+        let mut blocks = Vec::with_capacity(size*size*size);
+        let mut light = Vec::with_capacity(size*size*size);
+        for x in -1..SNAPSHOT_SIZE-1 {
+            for y in -1..SNAPSHOT_SIZE-1 {
+                for z in -1..SNAPSHOT_SIZE-1 {
+                    let p: BlockPos = (x+bx, y+by, z+bz).into();
+                    let chunk = &w.chunks.get(&p.as_chunk())?.chunk;
+                    let block = chunk.block_at(&p);
+                    let idx = palette.iter()
+                        .position(|b| *b == block)
+                        .unwrap_or_else(|| {
+                            palette.push(block);
+                            palette.len() - 1
+                        });
+                    blocks.push(idx as u16);
+                    light.push(*chunk.light_at(&p));
+                }
+            }
+        }
+        let looks = palette.into_iter()
+            .map(|id| w.registry.by_id(id).unwrap_or_else(|| w.registry.get("air").unwrap()))
+            .map(|b| BlockLook {
+                transparent: b.transparent,
+                semi_transparent: b.semi_transparent,
+                group_transparent: b.group_transparent,
+                no_render: b.no_render,
+                uvs: [Face::XPos, Face::XNeg, Face::YPos, Face::YNeg, Face::ZPos, Face::ZNeg]
+                    .map(|face| atlas.get_uv(b.face_texture(face))),
+            })
+            .collect();
+        Some(Self {
+            pos,
+            looks,
+            blocks,
+            light,
+            uv_dif: atlas.uv_dif(),
+        })
+    }
+
+    /// Index of the chunk relative position `x`, `y`, `z`, which may be in the border
+    fn index(x: i32, y: i32, z: i32) -> usize {
+        (((x + 1) * SNAPSHOT_SIZE + y + 1) * SNAPSHOT_SIZE + z + 1) as usize
+    }
+
+    fn block(&self, x: i32, y: i32, z: i32) -> u16 {
+        self.blocks[Self::index(x, y, z)]
+    }
+
+    fn look(&self, block: u16) -> &BlockLook {
+        &self.looks[block as usize]
+    }
+
+    fn light(&self, x: i32, y: i32, z: i32) -> f32 {
+        self.light[Self::index(x, y, z)].block() as f32 / 15.
+    }
+
+}
+
+/// Meshes of the first and second render pass of the snapshot's chunk
+pub fn make_mesh(s: &ChunkSnapshot) -> (MeshData, MeshData) {
 
     let mut verts1 = vec![];
     let mut uvs1 = vec![];
@@ -180,43 +332,23 @@ pub fn make_mesh(pos: ChunkPos, w: &VoxelData, reg: &ItemRegistry, atlas: &Textu
     let mut uvs2 = vec![];
     let mut light2 = vec![];
 
-    let uv_dif = atlas.uv_dif();
-
-    let (bx,by,bz) = pos.as_block().as_tuple();
+    let uv_dif = s.uv_dif;
 
     for x in 0..16i32 {
         for y in 0..16i32 {
             for z in 0..16i32 {
 
-                macro_rules! get {
-                    ($x:expr, $y:expr, $z:expr) => {{
-                        let p: BlockPos = ($x+bx, $y+by, $z+bz).into();
-                        w.block_at_any_state(&p)
-                            .or_else(|| {println!("{:?} {:?}",pos,p);None})
-                            .unwrap()
-                    }};
-                    (light $x:expr, $y:expr, $z:expr) => {{
-                        let p: BlockPos = ($x+bx, $y+by, $z+bz).into();
-                        w.light_at(&p).block() as f32 / 15.
-                    }};
-                }
-
-                let block = {
-                    let p: BlockPos = (x+bx, y+by, z+bz).into();
-                    w.block_at_any_state(&p)
-                        .or_else(|| {println!("{:?} {:?}",pos,p);None})
-                        .unwrap()
-                };
+                let id = s.block(x,y,z);
+                let block = s.look(id);
 
                 macro_rules! should_draw {
                     ($x:expr, $y:expr, $z:expr) => {{
-                        let b = get!($x,$y,$z);
-                        b.transparent && !(b.group_transparent && std::ptr::eq(b, block))
+                        let other = s.block($x,$y,$z);
+                        let b = s.look(other);
+                        b.transparent && !(b.group_transparent && other == id)
                     }};
                 }
 
-                let t = block.transparent;
-
                 if block.no_render {continue};
 
                 let xc = x as isize;
@@ -229,7 +361,7 @@ pub fn make_mesh(pos: ChunkPos, w: &VoxelData, reg: &ItemRegistry, atlas: &Textu
 
                 // y+ face
                 if should_draw!(x,y+1,z) {
-                    let l = get!(light x,y+1,z);
+                    let l = s.light(x,y+1,z);
                     light.extend([l].iter().cycle().take(6));
                     verts.extend_from_slice(&[
                         xc, yc, zc,
@@ -239,7 +371,7 @@ pub fn make_mesh(pos: ChunkPos, w: &VoxelData, reg: &ItemRegistry, atlas: &Textu
                         xc+1, yc, zc+1,
                         xc+1, yc, zc,
                     ]);
-                    let (u,v) = atlas.get_uv(block.texture.0);
+                    let (u,v) = block.uvs[Face::YPos as usize];
                     let (uh,vh) = (u+uv_dif,v+uv_dif);
                     uvs.extend_from_slice(&[
                         u, v,
@@ -254,7 +386,7 @@ pub fn make_mesh(pos: ChunkPos, w: &VoxelData, reg: &ItemRegistry, atlas: &Textu
                 // y- face
                 if should_draw!(x,y-1,z) {
                     let yc = yc - 1;
-                    let l = get!(light x,y-1,z);
+                    let l = s.light(x,y-1,z);
                     light.extend([l].iter().cycle().take(6));
                     verts.extend_from_slice(&[
                         xc, yc, zc,
@@ -264,7 +396,7 @@ pub fn make_mesh(pos: ChunkPos, w: &VoxelData, reg: &ItemRegistry, atlas: &Textu
                         xc+1, yc, zc,
                         xc+1, yc, zc+1,
                     ]);
-                    let (u,v) = atlas.get_uv(block.texture.2);
+                    let (u,v) = block.uvs[Face::YNeg as usize];
                     let (uh,vh) = (u+uv_dif,v+uv_dif);
                     uvs.extend_from_slice(&[
                         u, v,
@@ -276,13 +408,11 @@ pub fn make_mesh(pos: ChunkPos, w: &VoxelData, reg: &ItemRegistry, atlas: &Textu
                     ]);
                 }
 
-                // side faces are the same
-                let (u,v) = atlas.get_uv(block.texture.1);
-                let (uh,vh) = (u+uv_dif,v+uv_dif);
-
                 // x- face
                 if should_draw!(x-1,y,z) {
-                    let l = get!(light x-1,y,z);
+                    let (u,v) = block.uvs[Face::XNeg as usize];
+                    let (uh,vh) = (u+uv_dif,v+uv_dif);
+                    let l = s.light(x-1,y,z);
                     light.extend([l].iter().cycle().take(6));
                     verts.extend_from_slice(&[
                         xc, yc, zc,
@@ -304,8 +434,10 @@ pub fn make_mesh(pos: ChunkPos, w: &VoxelData, reg: &ItemRegistry, atlas: &Textu
 
                 // x+ face
                 if should_draw!(x+1,y,z) {
+                    let (u,v) = block.uvs[Face::XPos as usize];
+                    let (uh,vh) = (u+uv_dif,v+uv_dif);
                     let xc = xc + 1;
-                    let l = get!(light x+1,y,z);
+                    let l = s.light(x+1,y,z);
                     light.extend([l].iter().cycle().take(6));
                     verts.extend_from_slice(&[
                         xc, yc, zc,
@@ -327,8 +459,10 @@ pub fn make_mesh(pos: ChunkPos, w: &VoxelData, reg: &ItemRegistry, atlas: &Textu
 
                 // z- face
                 if should_draw!(x,y,z-1) {
+                    let (u,v) = block.uvs[Face::ZNeg as usize];
+                    let (uh,vh) = (u+uv_dif,v+uv_dif);
                     let yc = yc - 1; //?
-                    let l = get!(light x,y,z-1);
+                    let l = s.light(x,y,z-1);
                     light.extend([l].iter().cycle().take(6));
                     verts.extend_from_slice(&[
                         xc, yc, zc,
@@ -350,9 +484,11 @@ pub fn make_mesh(pos: ChunkPos, w: &VoxelData, reg: &ItemRegistry, atlas: &Textu
 
                 // z+ face
                 if should_draw!(x,y,z+1) {
+                    let (u,v) = block.uvs[Face::ZPos as usize];
+                    let (uh,vh) = (u+uv_dif,v+uv_dif);
                     let yc = yc - 1;//?
                     let zc = zc + 1;
-                    let l = get!(light x,y,z+1);
+                    let l = s.light(x,y,z+1);
                     light.extend([l].iter().cycle().take(6));
                     verts.extend_from_slice(&[
                         xc, yc, zc,
@@ -379,589 +515,6 @@ pub fn make_mesh(pos: ChunkPos, w: &VoxelData, reg: &ItemRegistry, atlas: &Textu
     let verts1 = verts1.into_iter().map(|v: isize| v as f32).collect::<Vec<_>>();
     let verts2 = verts2.into_iter().map(|v: isize| v as f32).collect::<Vec<_>>();
 
-    // println!("{:?} values in {} ms",verts.len()+uvs.len()+light.len(), now.elapsed().as_millis());
-
     ((verts1, uvs1, light1), (verts2, uvs2, light2))
 
 }
-
-pub fn make_mesh_old(pos: ChunkPos, w: &WorldData, reg: &ItemRegistry, atlas: &TextureAtlas) -> (Vec<f32>, Vec<f32>, Vec<f32>) {
-
-    let data = &w.blocks.chunks.get(&pos).unwrap().chunk.data;
-
-    let mut verts = vec![];
-    let mut uvs = vec![];
-    let mut light = vec![];
-
-    let uv_dif = atlas.uv_dif();
-
-    for x in 0..16 {
-        for y in 0..16 {
-            for z in 0..16 {
-
-                let block = &data[x][y][z];
-
-                let t = block.transparent;
-
-                if block.no_render {continue};
-
-                let xc = x as isize;
-                let yc = y as isize + 1;
-                let zc = z as isize;
-
-                // y+ face
-                if y == 15 || data[x][y+1][z].transparent /* != t */ {
-                    light.extend(&[1.,1.,1.,1.,1.,1.,]);
-                    verts.extend_from_slice(&[
-                        xc, yc, zc,
-                        xc, yc, zc+1,
-                        xc+1, yc, zc,
-                        xc, yc, zc+1,
-                        xc+1, yc, zc+1,
-                        xc+1, yc, zc,
-                    ]);
-                    let (u,v) = atlas.get_uv(block.texture.0);
-                    let (uh,vh) = (u+uv_dif,v+uv_dif);
-                    uvs.extend_from_slice(&[
-                        u, v,
-                        u, vh,
-                        uh, v,
-                        u, vh,
-                        uh, vh,
-                        uh, v,
-                    ]);
-                }
-
-                // y- face
-                if y == 0 || data[x][y-1][z].transparent /* != t */ {
-                    let yc = yc - 1;
-                    light.extend(&[1.,1.,1.,1.,1.,1.,]);
-                    verts.extend_from_slice(&[
-                        xc, yc, zc,
-                        xc+1, yc, zc,
-                        xc, yc, zc+1,
-                        xc, yc, zc+1,
-                        xc+1, yc, zc,
-                        xc+1, yc, zc+1,
-                    ]);
-                    let (u,v) = atlas.get_uv(block.texture.2);
-                    let (uh,vh) = (u+uv_dif,v+uv_dif);
-                    uvs.extend_from_slice(&[
-                        u, v,
-                        uh, v,
-                        u, vh,
-                        u, vh,
-                        uh, v,
-                        uh, vh,
-                    ]);
-                }
-
-                // side faces are the same
-                let (u,v) = atlas.get_uv(block.texture.1);
-                let (uh,vh) = (u+uv_dif,v+uv_dif);
-
-                // x- face
-                if x == 0 || data[x-1][y][z].transparent /* != t */ {
-                    light.extend(&[1.,1.,1.,1.,1.,1.,]);
-                    verts.extend_from_slice(&[
-                        xc, yc, zc,
-                        xc, yc-1, zc,
-                        xc, yc, zc+1,
-                        xc, yc-1, zc,
-                        xc, yc-1, zc+1,
-                        xc, yc, zc+1,
-                    ]);
-                    uvs.extend_from_slice(&[
-                        u, v,
-                        u, vh,
-                        uh, v,
-                        u, vh,
-                        uh, vh,
-                        uh, v,
-                    ]);
-                }
-
-                // x+ face
-                if x == 15 || data[x+1][y][z].transparent /* != t */ {
-                    let xc = xc + 1;
-                    light.extend(&[1.,1.,1.,1.,1.,1.,]);
-                    verts.extend_from_slice(&[
-                        xc, yc, zc,
-                        xc, yc, zc+1,
-                        xc, yc-1, zc,
-                        xc, yc-1, zc,
-                        xc, yc, zc+1,
-                        xc, yc-1, zc+1,
-                    ]);
-                    uvs.extend_from_slice(&[
-                        u, v,
-                        uh, v,
-                        u, vh,
-                        u, vh,
-                        uh, v,
-                        uh, vh,
-                    ]);
-                }
-
-                // z- face
-                if z == 0 || data[x][y][z-1].transparent /* != t */ {
-                    let yc = yc - 1; //?
-                    light.extend(&[1.,1.,1.,1.,1.,1.,]);
-                    verts.extend_from_slice(&[
-                        xc, yc, zc,
-                        xc, yc+1, zc,
-                        xc+1, yc, zc,
-                        xc+1, yc, zc,
-                        xc, yc+1, zc,
-                        xc+1, yc+1, zc,
-                    ]);
-                    uvs.extend_from_slice(&[
-                        uh, vh,
-                        uh, v,
-                        u, vh,
-                        u, vh,
-                        uh, v,
-                        u, v,
-                    ]);
-                }
-
-                // z+ face
-                if z == 15 || data[x][y][z+1].transparent /* != t */ {
-                    let yc = yc - 1;//?
-                    let zc = zc + 1;
-                    light.extend(&[1.,1.,1.,1.,1.,1.,]);
-                    verts.extend_from_slice(&[
-                        xc, yc, zc,
-                        xc+1, yc, zc,
-                        xc, yc+1, zc,
-                        xc+1, yc, zc,
-                        xc+1, yc+1, zc,
-                        xc, yc+1, zc,
-                    ]);
-                    uvs.extend_from_slice(&[
-                        uh, vh,
-                        u, vh,
-                        uh, v,
-                        u, vh,
-                        u, v,
-                        uh, v,
-                    ]);
-                }
-                
-            }
-        }
-    }
-
-    let verts = verts.into_iter().map(|v: isize| v as f32).collect::<Vec<_>>();
-
-    (verts, uvs, light)
-
-}
-
-
-pub fn make_mesh_hybrid(pos: ChunkPos, w: &WorldData, reg: &ItemRegistry, atlas: &TextureAtlas) -> (Vec<f32>, Vec<f32>, Vec<f32>) {
-
-    let now = std::time::Instant::now();
-
-    let mut verts = vec![];
-    let mut uvs = vec![];
-    let mut light = vec![];
-
-    let uv_dif = atlas.uv_dif();
-
-    let (bx,by,bz) = pos.as_block().as_tuple();
-
-    let data = &w.blocks.chunks.get(&pos).unwrap().chunk.data;
-
-    for x in 0..16 {
-        for z in 0..16 {
-            
-            let yplus = &w.blocks.chunks.get(&(pos+(0,1,0).into())).unwrap().chunk.data;
-            let y = 15;
-            let xc = x as isize;
-            let yc = y as isize + 1;
-            let zc = z as isize;
-            let block = &data[x][y][z];
-            if !block.no_render {
-    
-                // y+ face
-                if yplus[x][0][z].transparent /* != t */ {
-                    light.extend(&[1.,1.,1.,1.,1.,1.,]);
-                    verts.extend_from_slice(&[
-                        xc, yc, zc,
-                        xc, yc, zc+1,
-                        xc+1, yc, zc,
-                        xc, yc, zc+1,
-                        xc+1, yc, zc+1,
-                        xc+1, yc, zc,
-                    ]);
-                    let (u,v) = atlas.get_uv(block.texture.0);
-                    let (uh,vh) = (u+uv_dif,v+uv_dif);
-                    uvs.extend_from_slice(&[
-                        u, v,
-                        u, vh,
-                        uh, v,
-                        u, vh,
-                        uh, vh,
-                        uh, v,
-                    ]);
-                }
-            };
-
-            let yneg = &w.blocks.chunks.get(&(pos+(0,-1,0).into())).unwrap().chunk.data;
-            let y = 0;
-            let xc = x as isize;
-            let yc = y as isize + 1;
-            let zc = z as isize;
-            let block = &data[x][y][z];
-            if !block.no_render {
-                let xc = x as isize;
-                let yc = y as isize + 1;
-                let zc = z as isize;
-
-                // y- face
-                if yneg[x][15][z].transparent /* != t */ {
-                    let yc = yc - 1;
-                    light.extend(&[1.,1.,1.,1.,1.,1.,]);
-                    verts.extend_from_slice(&[
-                        xc, yc, zc,
-                        xc+1, yc, zc,
-                        xc, yc, zc+1,
-                        xc, yc, zc+1,
-                        xc+1, yc, zc,
-                        xc+1, yc, zc+1,
-                    ]);
-                    let (u,v) = atlas.get_uv(block.texture.2);
-                    let (uh,vh) = (u+uv_dif,v+uv_dif);
-                    uvs.extend_from_slice(&[
-                        u, v,
-                        uh, v,
-                        u, vh,
-                        u, vh,
-                        uh, v,
-                        uh, vh,
-                    ]);
-                }
-            }
-
-        }
-    }
-    
-    for x in 0..16 {
-        for y in 0..16 {
-            
-            let zplus = &w.blocks.chunks.get(&(pos+(0,0,1).into())).unwrap().chunk.data;
-            let z = 15;
-            let xc = x as isize;
-            let yc = y as isize + 1;
-            let zc = z as isize;
-            let block = &data[x][y][z];
-            if !block.no_render {
-                
-                // side faces are the same
-                let (u,v) = atlas.get_uv(block.texture.1);
-                let (uh,vh) = (u+uv_dif,v+uv_dif);
-    
-                // z+ face
-                if zplus[x][y][0].transparent /* != t */ {
-                    let yc = yc - 1;//?
-                    let zc = zc + 1;
-                    light.extend(&[1.,1.,1.,1.,1.,1.,]);
-                    verts.extend_from_slice(&[
-                        xc, yc, zc,
-                        xc+1, yc, zc,
-                        xc, yc+1, zc,
-                        xc+1, yc, zc,
-                        xc+1, yc+1, zc,
-                        xc, yc+1, zc,
-                    ]);
-                    uvs.extend_from_slice(&[
-                        uh, vh,
-                        u, vh,
-                        uh, v,
-                        u, vh,
-                        u, v,
-                        uh, v,
-                    ]);
-                }
-
-            };
-
-            let zneg = &w.blocks.chunks.get(&(pos+(0,0,-1).into())).unwrap().chunk.data;
-            let z = 0;
-            let xc = x as isize;
-            let yc = y as isize + 1;
-            let zc = z as isize;
-            let block = &data[x][y][z];
-            if !block.no_render {
-
-                // side faces are the same
-                let (u,v) = atlas.get_uv(block.texture.1);
-                let (uh,vh) = (u+uv_dif,v+uv_dif);
-
-                // z- face
-                if zneg[x][y][15].transparent /* != t */ {
-                    let yc = yc - 1; //?
-                    light.extend(&[1.,1.,1.,1.,1.,1.,]);
-                    verts.extend_from_slice(&[
-                        xc, yc, zc,
-                        xc, yc+1, zc,
-                        xc+1, yc, zc,
-                        xc+1, yc, zc,
-                        xc, yc+1, zc,
-                        xc+1, yc+1, zc,
-                    ]);
-                    uvs.extend_from_slice(&[
-                        uh, vh,
-                        uh, v,
-                        u, vh,
-                        u, vh,
-                        uh, v,
-                        u, v,
-                    ]);
-                }
-            }
-
-        }
-    }
-
-    for y in 0..16 {
-        for z in 0..16 {
-            
-            let xplus = &w.blocks.chunks.get(&(pos+(1,0,0).into())).unwrap().chunk.data;
-            let x = 15;
-            let xc = x as isize;
-            let yc = y as isize + 1;
-            let zc = z as isize;
-            let block = &data[x][y][z];
-            if !block.no_render {
-                
-                // side faces are the same
-                let (u,v) = atlas.get_uv(block.texture.1);
-                let (uh,vh) = (u+uv_dif,v+uv_dif);
-    
-                // x+ face
-                if xplus[0][y][z].transparent /* != t */ {
-                    let xc = xc + 1;
-                    light.extend(&[1.,1.,1.,1.,1.,1.,]);
-                    verts.extend_from_slice(&[
-                        xc, yc, zc,
-                        xc, yc, zc+1,
-                        xc, yc-1, zc,
-                        xc, yc-1, zc,
-                        xc, yc, zc+1,
-                        xc, yc-1, zc+1,
-                    ]);
-                    uvs.extend_from_slice(&[
-                        u, v,
-                        uh, v,
-                        u, vh,
-                        u, vh,
-                        uh, v,
-                        uh, vh,
-                    ]);
-                }
-
-            };
-
-            let xneg = &w.blocks.chunks.get(&(pos+(-1,0,0).into())).unwrap().chunk.data;
-            let x = 0;
-            let xc = x as isize;
-            let yc = y as isize + 1;
-            let zc = z as isize;
-            let block = &data[x][y][z];
-            if !block.no_render {
-
-                // side faces are the same
-                let (u,v) = atlas.get_uv(block.texture.1);
-                let (uh,vh) = (u+uv_dif,v+uv_dif);
-
-                // x- face
-                if xneg[15][y][z].transparent /* != t */ {
-                    light.extend(&[1.,1.,1.,1.,1.,1.,]);
-                    verts.extend_from_slice(&[
-                        xc, yc, zc,
-                        xc, yc-1, zc,
-                        xc, yc, zc+1,
-                        xc, yc-1, zc,
-                        xc, yc-1, zc+1,
-                        xc, yc, zc+1,
-                    ]);
-                    uvs.extend_from_slice(&[
-                        u, v,
-                        u, vh,
-                        uh, v,
-                        u, vh,
-                        uh, vh,
-                        uh, v,
-                    ]);
-                }
-            }
-
-        }
-    }
-    
-    for x in 0..16 {
-        for y in 0..16 {
-            for z in 0..16 {
-
-                let block = &data[x][y][z];
-
-                let t = block.transparent;
-
-                if block.no_render {continue};
-
-                let xc = x as isize;
-                let yc = y as isize + 1;
-                let zc = z as isize;
-
-                // y+ face
-                if y != 15 && data[x][y+1][z].transparent /* != t */ {
-                    light.extend(&[1.,1.,1.,1.,1.,1.,]);
-                    verts.extend_from_slice(&[
-                        xc, yc, zc,
-                        xc, yc, zc+1,
-                        xc+1, yc, zc,
-                        xc, yc, zc+1,
-                        xc+1, yc, zc+1,
-                        xc+1, yc, zc,
-                    ]);
-                    let (u,v) = atlas.get_uv(block.texture.0);
-                    let (uh,vh) = (u+uv_dif,v+uv_dif);
-                    uvs.extend_from_slice(&[
-                        u, v,
-                        u, vh,
-                        uh, v,
-                        u, vh,
-                        uh, vh,
-                        uh, v,
-                    ]);
-                }
-
-                // y- face
-                if y != 0 && data[x][y-1][z].transparent /* != t */ {
-                    let yc = yc - 1;
-                    light.extend(&[1.,1.,1.,1.,1.,1.,]);
-                    verts.extend_from_slice(&[
-                        xc, yc, zc,
-                        xc+1, yc, zc,
-                        xc, yc, zc+1,
-                        xc, yc, zc+1,
-                        xc+1, yc, zc,
-                        xc+1, yc, zc+1,
-                    ]);
-                    let (u,v) = atlas.get_uv(block.texture.2);
-                    let (uh,vh) = (u+uv_dif,v+uv_dif);
-                    uvs.extend_from_slice(&[
-                        u, v,
-                        uh, v,
-                        u, vh,
-                        u, vh,
-                        uh, v,
-                        uh, vh,
-                    ]);
-                }
-
-                // side faces are the same
-                let (u,v) = atlas.get_uv(block.texture.1);
-                let (uh,vh) = (u+uv_dif,v+uv_dif);
-
-                // x- face
-                if x != 0 && data[x-1][y][z].transparent /* != t */ {
-                    light.extend(&[1.,1.,1.,1.,1.,1.,]);
-                    verts.extend_from_slice(&[
-                        xc, yc, zc,
-                        xc, yc-1, zc,
-                        xc, yc, zc+1,
-                        xc, yc-1, zc,
-                        xc, yc-1, zc+1,
-                        xc, yc, zc+1,
-                    ]);
-                    uvs.extend_from_slice(&[
-                        u, v,
-                        u, vh,
-                        uh, v,
-                        u, vh,
-                        uh, vh,
-                        uh, v,
-                    ]);
-                }
-
-                // x+ face
-                if x != 15 && data[x+1][y][z].transparent /* != t */ {
-                    let xc = xc + 1;
-                    light.extend(&[1.,1.,1.,1.,1.,1.,]);
-                    verts.extend_from_slice(&[
-                        xc, yc, zc,
-                        xc, yc, zc+1,
-                        xc, yc-1, zc,
-                        xc, yc-1, zc,
-                        xc, yc, zc+1,
-                        xc, yc-1, zc+1,
-                    ]);
-                    uvs.extend_from_slice(&[
-                        u, v,
-                        uh, v,
-                        u, vh,
-                        u, vh,
-                        uh, v,
-                        uh, vh,
-                    ]);
-                }
-
-                // z- face
-                if z != 0 && data[x][y][z-1].transparent /* != t */ {
-                    let yc = yc - 1; //?
-                    light.extend(&[1.,1.,1.,1.,1.,1.,]);
-                    verts.extend_from_slice(&[
-                        xc, yc, zc,
-                        xc, yc+1, zc,
-                        xc+1, yc, zc,
-                        xc+1, yc, zc,
-                        xc, yc+1, zc,
-                        xc+1, yc+1, zc,
-                    ]);
-                    uvs.extend_from_slice(&[
-                        uh, vh,
-                        uh, v,
-                        u, vh,
-                        u, vh,
-                        uh, v,
-                        u, v,
-                    ]);
-                }
-
-                // z+ face
-                if z != 15 && data[x][y][z+1].transparent /* != t */ {
-                    let yc = yc - 1;//?
-                    let zc = zc + 1;
-                    light.extend(&[1.,1.,1.,1.,1.,1.,]);
-                    verts.extend_from_slice(&[
-                        xc, yc, zc,
-                        xc+1, yc, zc,
-                        xc, yc+1, zc,
-                        xc+1, yc, zc,
-                        xc+1, yc+1, zc,
-                        xc, yc+1, zc,
-                    ]);
-                    uvs.extend_from_slice(&[
-                        uh, vh,
-                        u, vh,
-                        uh, v,
-                        u, vh,
-                        u, v,
-                        uh, v,
-                    ]);
-                }
-                
-            }
-        }
-    }
-
-    let verts = verts.into_iter().map(|v: isize| v as f32).collect::<Vec<_>>();
-    
-    // println!("{:?} values in {} ms",verts.len()+uvs.len()+light.len(), now.elapsed().as_millis());
-
-    (verts, uvs, light)
-
-}
\ No newline at end of file
@@ -1,6 +1,9 @@
 pub mod chunk;
+#[cfg(feature = "client")]
 pub mod meshing;
 pub mod lighting;
+pub mod palette;
+pub mod block_entity;
 pub mod gen;
 
 pub use lighting::*;
\ No newline at end of file
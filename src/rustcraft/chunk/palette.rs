@@ -0,0 +1,238 @@
+use std::io;
+use crate::prelude::*;
+
+/// Number of blocks in a chunk
+const VOLUME: usize = 16 * 16 * 16;
+
+/// Blocks of a chunk as indices into a palette of the block states it contains.
+/// Indices are bit-packed with just enough bits for the palette,
+/// a chunk made of a single block needs no indices at all.
+#[derive(Clone)]
+pub struct BlockStorage {
+    /// Numeric ids of the block states, see `BlockRegistry`
+    palette: Vec<BlockId>,
+    /// Bits per index, 0 while the palette has a single entry
+    bits: u32,
+    /// Packed indices in x-y-z order, an index never spans two words
+    words: Vec<u64>,
+}
+
+impl BlockStorage {
+
+    /// Storage with every block set to `block`
+    pub fn filled(block: BlockId) -> Self {
+        Self {
+            palette: vec![block],
+            bits: 0,
+            words: vec![],
+        }
+    }
+
+    /// Storage from a palette and one index into it per block in x-y-z order, as saved.
+    /// Fails if the palette is empty or the indices are not one per block within the palette.
+    pub fn from_palette(palette: Vec<BlockId>, indices: &[u16]) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        if palette.is_empty() {
+            return Err(invalid("empty block palette".into()))
+        }
+        if indices.len() != VOLUME {
+            return Err(invalid(format!("{} block indices instead of {}", indices.len(), VOLUME)))
+        }
+        if let Some(i) = indices.iter().find(|i| **i as usize >= palette.len()) {
+            return Err(invalid(format!("block index {} is outside the palette of {}", i, palette.len())))
+        }
+        Ok(Self::pack(palette, indices))
+    }
+
+    /// Block states that may appear in the chunk, some of them possibly no longer used
+    pub fn palette(&self) -> &[BlockId] {
+        &self.palette
+    }
+
+    /// Palette index of every block in x-y-z order
+    pub fn indices(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..VOLUME).map(move |i| self.get_index(i) as u16)
+    }
+
+    /// Whether every block is the same, without looking at each of them
+    pub fn is_single(&self) -> bool {
+        self.bits == 0
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> BlockId {
+        self.palette[self.get_index(Self::pos_index(x, y, z))]
+    }
+
+    /// Set the block at `x`, `y`, `z`, returns false if it already was `block`
+    pub fn set(&mut self, x: usize, y: usize, z: usize, block: BlockId) -> bool {
+        let i = Self::pos_index(x, y, z);
+        if self.palette[self.get_index(i)] == block {
+            return false
+        }
+        let idx = match self.palette.iter().position(|b| *b == block) {
+            Some(idx) => idx,
+            None => {
+                if self.palette.len() == 1 << self.bits {
+                    // reuse the room of states nothing refers to anymore before growing
+                    self.compact();
+                }
+                self.palette.push(block);
+                let bits = Self::bits_for(self.palette.len());
+                if bits != self.bits {
+                    self.repack(bits);
+                }
+                self.palette.len() - 1
+            }
+        };
+        self.set_index(i, idx);
+        true
+    }
+
+    /// Drop the palette entries no block uses, going back to a single value if only one is left
+    pub fn compact(&mut self) {
+        if self.bits == 0 {
+            return
+        }
+        let mut used = vec![false; self.palette.len()];
+        for i in 0..VOLUME {
+            used[self.get_index(i)] = true;
+        }
+        if used.iter().all(|u| *u) {
+            return
+        }
+        let mut remap = vec![0; self.palette.len()];
+        let mut palette = Vec::with_capacity(self.palette.len());
+        for (old, block) in self.palette.iter().enumerate() {
+            if used[old] {
+                remap[old] = palette.len();
+                palette.push(*block);
+            }
+        }
+        let indices = self.indices().map(|idx| remap[idx as usize] as u16).collect::<Vec<_>>();
+        *self = Self::pack(palette, &indices);
+    }
+
+    /// Storage from a palette and indices known to fit it
+    fn pack(palette: Vec<BlockId>, indices: &[u16]) -> Self {
+        let bits = Self::bits_for(palette.len());
+        let mut storage = Self {
+            palette,
+            bits,
+            words: vec![0; Self::word_count(bits)],
+        };
+        if bits > 0 {
+            for (i, idx) in indices.iter().enumerate() {
+                storage.set_index(i, *idx as usize);
+            }
+        }
+        storage
+    }
+
+    fn pos_index(x: usize, y: usize, z: usize) -> usize {
+        (x * 16 + y) * 16 + z
+    }
+
+    fn bits_for(palette_len: usize) -> u32 {
+        match palette_len {
+            0 | 1 => 0,
+            n => usize::BITS - (n - 1).leading_zeros(),
+        }
+    }
+
+    fn word_count(bits: u32) -> usize {
+        if bits == 0 {
+            0
+        } else {
+            let per_word = (64 / bits) as usize;
+            (VOLUME + per_word - 1) / per_word
+        }
+    }
+
+    fn get_index(&self, i: usize) -> usize {
+        if self.bits == 0 {
+            return 0
+        }
+        let per_word = (64 / self.bits) as usize;
+        let shift = (i % per_word) as u32 * self.bits;
+        ((self.words[i / per_word] >> shift) & ((1 << self.bits) - 1)) as usize
+    }
+
+    fn set_index(&mut self, i: usize, idx: usize) {
+        let per_word = (64 / self.bits) as usize;
+        let shift = (i % per_word) as u32 * self.bits;
+        let mask = ((1u64 << self.bits) - 1) << shift;
+        let word = &mut self.words[i / per_word];
+        *word = (*word & !mask) | (((idx as u64) << shift) & mask);
+    }
+
+    fn repack(&mut self, bits: u32) {
+        let indices = self.indices().collect::<Vec<_>>();
+        self.bits = bits;
+        self.words = vec![0; Self::word_count(bits)];
+        for (i, idx) in indices.into_iter().enumerate() {
+            self.set_index(i, idx as usize);
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits_for_palette_sizes() {
+        assert_eq!(BlockStorage::bits_for(1), 0);
+        assert_eq!(BlockStorage::bits_for(2), 1);
+        assert_eq!(BlockStorage::bits_for(3), 2);
+        assert_eq!(BlockStorage::bits_for(4), 2);
+        assert_eq!(BlockStorage::bits_for(5), 3);
+        assert_eq!(BlockStorage::bits_for(16), 4);
+        assert_eq!(BlockStorage::bits_for(17), 5);
+    }
+
+    #[test]
+    fn growing_the_palette_keeps_blocks() {
+        let mut storage = BlockStorage::filled(0);
+        // one new state per block, crossing several index widths
+        for i in 0..40 {
+            assert!(storage.set(i % 16, i / 16, 0, i as BlockId + 1));
+        }
+        assert_eq!(storage.bits, 6);
+        for i in 0..40 {
+            assert_eq!(storage.get(i % 16, i / 16, 0), i as BlockId + 1);
+        }
+        assert_eq!(storage.get(15, 15, 15), 0);
+        assert!(!storage.set(0, 0, 0, 1));
+    }
+
+    #[test]
+    fn compact_drops_unused_states() {
+        let mut storage = BlockStorage::filled(0);
+        storage.set(1, 2, 3, 7);
+        storage.set(4, 5, 6, 9);
+        storage.set(1, 2, 3, 0);
+        storage.compact();
+        assert_eq!(storage.palette(), &[0, 9]);
+        assert_eq!(storage.bits, 1);
+        assert_eq!(storage.get(4, 5, 6), 9);
+        assert_eq!(storage.get(1, 2, 3), 0);
+        storage.set(4, 5, 6, 0);
+        storage.compact();
+        assert!(storage.is_single());
+        assert_eq!(storage.get(4, 5, 6), 0);
+    }
+
+    #[test]
+    fn from_palette_rejects_bad_data() {
+        assert!(BlockStorage::from_palette(vec![], &[0; VOLUME]).is_err());
+        assert!(BlockStorage::from_palette(vec![3], &[0; 10]).is_err());
+        let mut indices = vec![0; VOLUME];
+        indices[100] = 2;
+        assert!(BlockStorage::from_palette(vec![3, 4], &indices).is_err());
+        indices[100] = 1;
+        let storage = BlockStorage::from_palette(vec![3, 4], &indices).unwrap();
+        assert_eq!(storage.indices().collect::<Vec<_>>(), indices);
+    }
+
+}
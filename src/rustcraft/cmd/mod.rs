@@ -12,9 +12,12 @@ impl From<()> for PErr {
 
 #[derive(Debug)]
 pub enum Cmd {
-    Give { id: String, count: usize },
+    /// Give items to `player`, or to whoever ran the command
+    Give { id: String, count: usize, player: Option<String> },
     Summon { id: String },
     ForceLoad { pos: ChunkPos },
+    Save,
+    Backup,
 }
 
 impl std::str::FromStr for Cmd {
@@ -36,12 +39,21 @@ fn parse(mut s: Scanner) -> Result<Cmd, PErr> {
                     return Err(PErr);
                 }
                 let count = count as usize;
-                return Ok(Cmd::Give{ id, count })
+                let player = s.get_iden().ok();
+                return Ok(Cmd::Give{ id, count, player })
             },
             "summon" => {
                 let id = s.get_iden()?;
                 return Ok(Cmd::Summon { id })
             }
+            "forceload" => {
+                let x = s.get_integer()? as i32;
+                let y = s.get_integer()? as i32;
+                let z = s.get_integer()? as i32;
+                return Ok(Cmd::ForceLoad { pos: (x, y, z).into() })
+            }
+            "save" => return Ok(Cmd::Save),
+            "backup" => return Ok(Cmd::Backup),
             _ => return Err(PErr)
         }
     };
@@ -49,20 +61,38 @@ fn parse(mut s: Scanner) -> Result<Cmd, PErr> {
 }
 
 impl Cmd {
-    pub fn exec(&self, world: &mut WorldData, idata: &data::IData,) {
+    /// Run the command for the player named `sender`, `None` when run from the server console.
+    /// Returns the text to show to whoever ran it.
+    pub fn exec(&self, world: &mut WorldData, content: &Content, sender: Option<&str>) -> String {
         match self {
-            Self::Give { id, count } => {
-                if let Ok(pdata) = world.entities.ecs.query_one_mut::<&mut crate::PlayerData>(world.entities.player) {
-                    let mut count = *count;
-                    while count > 0 {
-                        let rem = count.min(64);
-                        count -= rem;
-                        pdata.inventory.merge(&mut ItemStack::of(idata.content.items.get(id).clone(), rem).into());
+            Self::Give { id, count, player } => {
+                let target = match player.as_deref().or(sender) {
+                    Some(name) => name,
+                    None => return "Give needs a player name when run from the console".into(),
+                };
+                let ent = match world.players.get(target) {
+                    Some(ent) => *ent,
+                    None => return format!("No player named {}", target),
+                };
+                let item = match content.items.items.get(id) {
+                    Some(item) => item,
+                    None => return format!("No item {}", id),
+                };
+                match world.entities.ecs.query_one_mut::<&mut crate::PlayerData>(ent) {
+                    Ok(pdata) => {
+                        let mut rem = *count;
+                        while rem > 0 {
+                            let n = rem.min(64);
+                            rem -= n;
+                            pdata.inventory.merge(&mut ItemStack::of(item.clone(), n).into());
+                        }
+                        format!("Gave {} {} to {}", count, id, target)
                     }
+                    Err(_) => format!("{} has no inventory", target),
                 }
             }
             Self::Summon { id } => {
-                /* if let Some(template) = idata.content.entities.entities.get(id) {
+                /* if let Some(template) = content.entities.entities.get(id) {
                     let mut builder = hecs::EntityBuilder::new();
                     let pos = Position::new((50,55,50).into(), (0.9,0.9,0.9).into());
                     let aabb = pos.get_aabb();
@@ -73,16 +103,29 @@ impl Cmd {
                 } else {
                     println!("No such entity template {}", id);
                 } */
+                format!("Cannot summon {}, summoning is not supported yet", id)
             }
             Self::ForceLoad { pos } => {
                 // actually do something useful
-                if !world.force_loaded.insert(*pos) {
-                    println!("Force loading {:?}", pos);
+                if world.force_loaded.insert(*pos) {
+                    format!("Force loading {:?}", pos)
                 } else {
                     world.force_loaded.remove(pos);
-                    println!("Removed force loading for {:?}", pos);
+                    format!("Removed force loading for {:?}", pos)
+                }
+            }
+            Self::Save => {
+                match world.save_all() {
+                    Ok(()) => "Saving world".into(),
+                    Err(e) => format!("Failed to save world: {}", e),
+                }
+            }
+            Self::Backup => {
+                match world.backup() {
+                    Ok(()) => "Backing up world".into(),
+                    Err(e) => format!("Failed to back up world: {}", e),
                 }
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -2,19 +2,20 @@
 use rand::prelude::*;
 use super::*;
 
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct WanderingAI {
     dir: Vector3<f32>,
     walk_time: f32,
 }
 
 impl WanderingAI {
-    pub fn new() -> Self {
-        Self { dir: Self::rnd(), walk_time: 0. }
+    pub fn new(rng: &mut impl Rng) -> Self {
+        Self { dir: Self::rnd(rng), walk_time: 0. }
     }
-    pub fn update(&mut self, delta: f32) {
+    pub fn update(&mut self, delta: f32, rng: &mut impl Rng) {
         self.walk_time += delta;
-        if self.walk_time > 7. /*&& random::<f32>() < 0.005*/ {
-            self.dir = Self::rnd();
+        if self.walk_time > 7. /*&& rng.gen::<f32>() < 0.005*/ {
+            self.dir = Self::rnd(rng);
             self.walk_time = 0.;
         }
         if self.walk_time > 5. {
@@ -22,8 +23,8 @@ impl WanderingAI {
         }
     }
     pub fn heading(&self) -> &Vector3<f32> {&self.dir}
-    fn rnd() -> Vector3<f32> {
-        let rad: f32 = random::<f32>() * std::f32::consts::TAU;
+    fn rnd(rng: &mut impl Rng) -> Vector3<f32> {
+        let rad: f32 = rng.gen::<f32>() * std::f32::consts::TAU;
         Vector3 {
             x: rad.sin(),
             y: 0.,
@@ -32,8 +33,11 @@ impl WanderingAI {
     }
 
     pub fn system_update(data: &mut crate::WorldData, delta: f32) {
-        for (_ent, (phys, ai)) in data.entities.ecs.query_mut::<(&mut Physics, &mut WanderingAI)>() {
-            ai.update(delta);
+        for (_ent, (pos, phys, ai)) in data.entities.ecs.query_mut::<(&Position, &mut Physics, &mut WanderingAI)>() {
+            if data.tickets.status_at(pos.pos.as_chunk()) < ChunkStatus::EntityTicking {
+                continue
+            }
+            ai.update(delta, &mut data.rng);
             phys.apply_force_continuous(delta * 40., ai.heading());
         }
     }
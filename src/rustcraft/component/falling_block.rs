@@ -1,6 +1,7 @@
 
 use super::*;
 
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct FallingBlock {
     /* pub block: Block */
 }
@@ -19,36 +19,66 @@ impl From<ItemStack> for ItemCmp {
 
 impl ItemCmp {
 
+    /// Spawn `stack` as an item lying at `pos`
+    pub fn spawn(data: &mut crate::WorldData, pos: WorldPos, stack: ItemStack) -> hecs::Entity {
+        let ipos = Position::new(pos, (0.3,0.3,0.3).into());
+        let aabb = ipos.get_aabb();
+        let model = ModelDesc::Item(stack.item.id().to_owned());
+        let ent = data.entities.ecs.spawn((
+            ipos,
+            Physics::new(),
+            ItemCmp::from(stack),
+            model,
+        ));
+        data.entities.tree.insert(ent, ent, &aabb);
+        ent
+    }
+
     pub fn system_tick_age_items(data: &mut crate::WorldData) {
 
         let mut despawn = vec![];
-        let mut picked_up = vec![];
         let mut update = HashMap::new();
-        if let Ok(pos) = data.entities.ecs.query_one_mut::<&Position>(data.entities.player).cloned() {
+        let players = data.entities.ecs.query_mut::<&Position>()
+            .with::<PlayerData>()
+            .into_iter()
+            .map(|(ent, pos)| (ent, pos.clone()))
+            .collect::<Vec<_>>();
+        // an item goes to the first player that reaches it, even if only part of it fits
+        let mut taken = HashSet::new();
+        for (player, pos) in players {
             let mut aabb = pos.get_aabb();
             aabb.extend_radius(2.);
+            let mut picked_up = vec![];
             for ent in data.entities.tree.query(&aabb) {
+                if taken.contains(ent) {
+                    continue
+                }
                 if let Ok((ipos, item)) = data.entities.ecs.query_one_mut::<(&Position,&ItemCmp)>(*ent) {
                     if item.age > 3 && ipos.pos.distance(pos.pos.0) < 1.5+0.4 {
-                        picked_up.push((ent, Some(item.item.clone())));
+                        picked_up.push((*ent, Some(item.item.clone())));
                     }
                 }
             }
-        }
-        if let Ok(pdata) = data.entities.ecs.query_one_mut::<&mut PlayerData>(data.entities.player) {
-            for (ent, mut stack) in picked_up {
-                pdata.inventory.merge(&mut stack);
-                if stack.is_none() {
-                    despawn.push(*ent);
-                } else {
-                    update.insert(ent, stack.unwrap());
+            if let Ok(pdata) = data.entities.ecs.query_one_mut::<&mut PlayerData>(player) {
+                for (ent, mut stack) in picked_up {
+                    taken.insert(ent);
+                    pdata.inventory.merge(&mut stack);
+                    match stack {
+                        None => despawn.push(ent),
+                        Some(stack) => {
+                            update.insert(ent, stack);
+                        }
+                    }
                 }
             }
         }
-        for (ent, item) in data.entities.ecs.query_mut::<&mut ItemCmp>() {
+        for (ent, (pos, item)) in data.entities.ecs.query_mut::<(&Position, &mut ItemCmp)>() {
             if let Some(stack) = update.remove(&ent) {
                 item.item = stack;
             }
+            if data.tickets.status_at(pos.pos.as_chunk()) < ChunkStatus::EntityTicking {
+                continue
+            }
             item.age += 1;
             if item.age > 20 * LIVE_SECS {
                 despawn.push(ent);
@@ -8,6 +8,7 @@ mod item;
 mod player;
 mod model;
 mod path_finding;
+mod net;
 
 pub use path_finding::*;
 pub use crate::prelude::*;
@@ -19,6 +20,7 @@ pub use viewable::*;
 pub use ai::*;
 pub use item::*;
 pub use player::*;
+pub use net::*;
 
 #[derive(Default)]
 pub struct ComponentRegistry {
@@ -1,15 +1,48 @@
 
+#[cfg(feature = "client")]
 use crate::util::Drawable;
+#[cfg(feature = "client")]
 use crate::static_prg::StaticProgram;
 use rand::prelude::*;
 use super::*;
 
+#[cfg(feature = "client")]
 #[derive(derive_more::From)]
 pub struct Model {
     pub model: Box<dyn Drawable>
 }
 
+/// Serializable description of a `Model`, materialised into one on the client
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub enum ModelDesc {
+    /// Item or block as it is shown in the inventory
+    Item(String),
+}
+
+#[cfg(feature = "client")]
 impl Model {
+    /// Add a `Model` to every entity that only has a `ModelDesc`
+    pub fn system_materialize(data: &mut WorldData, item_cubes: &Arc<VAO>, offsets: &HashMap<String, i32>) {
+        let to_add = data.entities.ecs.query_mut::<hecs::Without<Model, &ModelDesc>>()
+            .into_iter()
+            .map(|(ent, desc)| (ent, desc.clone()))
+            .collect::<Vec<_>>();
+        for (ent, desc) in to_add {
+            let model = match desc {
+                ModelDesc::Item(id) => {
+                    if let Some(offset) = offsets.get(&id) {
+                        box util::RenderedItem {
+                            vao: item_cubes.clone(),
+                            offset: *offset,
+                        } as Box<dyn Drawable>
+                    } else {
+                        continue
+                    }
+                }
+            };
+            let _ = data.entities.ecs.insert_one(ent, Model::from(model));
+        }
+    }
     pub fn system_render(data: &mut WorldData, program: &mut StaticProgram) {
         program.load_light(1.);
         for (ent, (model, pos)) in data.entities.ecs.query_mut::<(&Model, &Position)>() {
@@ -0,0 +1,54 @@
+use super::*;
+
+/// Identifies an entity across the server and its clients
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct NetId(pub u32);
+
+/// Moves a replicated entity smoothly from where it was shown towards the latest position sent by the server
+#[derive(Clone, Debug)]
+pub struct Interpolation {
+    from: WorldPos,
+    to: WorldPos,
+    vel: Vector3<f32>,
+    /// Ticks since the latest position arrived
+    progress: f32,
+}
+
+impl Interpolation {
+
+    pub fn new(pos: WorldPos, vel: Vector3<f32>) -> Self {
+        Self {
+            from: pos,
+            to: pos,
+            vel,
+            progress: 1.,
+        }
+    }
+
+    /// Start moving from `current` towards `to`
+    pub fn retarget(&mut self, current: WorldPos, to: WorldPos, vel: Vector3<f32>) {
+        self.from = current;
+        self.to = to;
+        self.vel = vel;
+        self.progress = 0.;
+    }
+
+    fn pos(&self) -> WorldPos {
+        if self.progress <= 1. {
+            (self.from.0 + (self.to.0 - self.from.0) * self.progress).into()
+        } else {
+            // the next update is late, keep going in the same direction for a while
+            let late = (self.progress - 1.).min(1.) * consts::TICK_DURATION.as_secs_f32();
+            (self.to.0 + self.vel * late).into()
+        }
+    }
+
+    pub fn system_update(data: &mut WorldData, delta: f32) {
+        let ticks = delta / consts::TICK_DURATION.as_secs_f32();
+        for (_ent, (pos, interp)) in data.entities.ecs.query_mut::<(&mut Position, &mut Interpolation)>() {
+            interp.progress += ticks;
+            pos.pos = interp.pos();
+        }
+    }
+
+}
@@ -36,6 +36,7 @@ impl FollowEntity {
 
 const MAX_RADIUS: f32 = 16.;
 
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct PathFinding {
     target: Option<BlockPos>,
     path: Option<Vec<BlockPos>>,
@@ -53,6 +54,9 @@ impl PathFinding {
 
     pub fn system_update_tick(data: &mut crate::WorldData) {
         for (_ent, (pos, pf)) in data.entities.ecs.query_mut::<(&mut Position, &mut PathFinding)>() {
+            if data.tickets.status_at(pos.pos.as_chunk()) < ChunkStatus::EntityTicking {
+                continue
+            }
             if pf.path.is_none() && pf.target.is_some() {
                 if pf.search_delay == 0 {
                     pf.path = create_path(pos.pos.as_block(), pf.target.unwrap(), &data.blocks);
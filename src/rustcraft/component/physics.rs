@@ -2,19 +2,19 @@
 use crate::world::VoxelData;
 use super::*;
 
-#[derive(Clone, serde::Deserialize)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct Physics {
     #[serde(default = "util::bool_true")]
     pub gravity: bool,
     #[serde(skip, default = "util::vec_f32_zero")]
     force: Vector3<f32>,
-    #[serde(skip, default = "util::vec_f32_zero")]
+    #[serde(default = "util::vec_f32_zero")]
     vel: Vector3<f32>,
-    #[serde(skip)]
+    #[serde(default)]
     grounded: bool,
     #[serde(skip)]
     edge_stop: bool,
-    #[serde(skip)]
+    #[serde(default)]
     pub freecam: bool,
 }
 
@@ -42,6 +42,7 @@ impl Physics {
     }
 
     pub fn is_grounded(&self) -> bool {self.grounded}
+    pub fn velocity(&self) -> Vector3<f32> {self.vel}
 
     pub fn apply_force_once(&mut self, f: &Vector3<f32>) {
         self.vel += *f;
@@ -189,7 +190,11 @@ impl Physics {
     }
 
     pub fn system_update(data: &mut WorldData, delta: f32) {
-        for (ent, (pos, phys)) in data.entities.ecs.query_mut::<(&mut Position, &mut Physics)>() {
+        // players are moved by their inputs
+        for (ent, (pos, phys)) in data.entities.ecs.query_mut::<(&mut Position, &mut Physics)>().without::<PlayerData>() {
+            if data.tickets.status_at(pos.pos.as_chunk()) < ChunkStatus::EntityTicking {
+                continue
+            }
             if phys.update(pos, delta, &data.blocks) {
                 data.entities.tree.update(ent, &pos.get_aabb());
             }
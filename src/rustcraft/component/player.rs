@@ -1,5 +1,7 @@
 
 use crate::player::inventory::PlayerInventory;
+use crate::world::VoxelData;
+use super::*;
 
 #[derive(Default)]
 pub struct PlayerData {
@@ -10,4 +12,43 @@ impl PlayerData {
 
     pub fn new() -> Self {Self::default()}
 
-}
\ No newline at end of file
+}
+/// Movement controls of one client frame.
+/// The client applies them immediately and the server repeats them to get the authoritative position.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PlayerInput {
+    /// Numbered in the order the client produced them
+    pub seq: u32,
+    /// Frame duration in seconds
+    pub delta: f32,
+    pub pitch: f32,
+    pub yaw: f32,
+    /// Normalized horizontal walking direction
+    pub movement: Vector3<f32>,
+    pub jump: bool,
+    pub sneak: bool,
+}
+
+impl PlayerInput {
+
+    /// Whether the values could have come from an unmodified client
+    pub fn is_valid(&self) -> bool {
+        // written so NaN fails every check
+        self.delta >= 0. && self.delta <= consts::MAX_FRAME_DELTA
+            && self.pitch.is_finite() && self.yaw.is_finite()
+            && self.movement.magnitude2() <= 1.001
+    }
+
+    /// Advance the player by one frame
+    pub fn apply(&self, pos: &mut Position, phys: &mut Physics, blocks: &VoxelData) {
+        pos.rot.x = Deg(self.pitch);
+        pos.rot.y = Deg(self.yaw);
+        phys.set_edge_stop(self.sneak);
+        if self.jump {
+            phys.try_jump(self.delta);
+        }
+        phys.apply_force_continuous(self.delta, &(self.movement * 40.));
+        phys.update(pos, self.delta, blocks);
+    }
+
+}
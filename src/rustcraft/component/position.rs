@@ -1,9 +1,9 @@
 
+#[cfg(feature = "client")]
 use crate::lines::LineProgram;
-use crate::Program;
 use super::*;
 
-#[derive(Clone)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct Position {
     pub size: Vector3<f32>,
     pub pos: WorldPos,
@@ -65,12 +65,13 @@ impl Position {
         }.normalize()
     }
 
+    #[cfg(feature = "client")]
     pub fn system_draw_bounding_boxes(world: &mut WorldData, program: &mut LineProgram) {
         program.enable();
         program.bind();
         program.load_color(&(0.8,0.8,0.8,1.0).into());
         for (ent, pos) in world.entities.ecs.query_mut::<&Position>() {
-            if ent == world.entities.player {continue};
+            if Some(ent) == world.entities.player {continue};
             program.load_transform(&(Matrix4::from_translation(pos.pos.0)
             * Matrix4::from_nonuniform_scale(pos.size.x, pos.size.y, pos.size.z)));
             program.draw();
@@ -1,7 +1,7 @@
 
 use super::*;
 
-#[derive(Clone, serde::Deserialize)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct View {
     #[serde(default = "util::vec_f32_zero")]
     pub offset: Vector3<f32>,
@@ -8,6 +8,7 @@ pub struct BaseMod;
 impl ContentMod for BaseMod {
 
     fn name(&mut self) -> &str {"rustcraft"}
+    fn version(&mut self) -> &str {env!("CARGO_PKG_VERSION")}
     
     /* fn register_components(&mut self, cnt: &mut ContentBuilder) {
         cnt.components.register("Physics".into(), Physics::new());
@@ -36,6 +37,10 @@ impl ContentMod for BaseMod {
             cnt.behaviors.behaviors.insert("update/gravity".into(), FallingBlock::behaviour_on_update)
             .is_none()
         );
+        assert!(
+            cnt.behaviors.behaviors.insert("use/chest".into(), super::chest_use)
+            .is_none()
+        );
     }
     
     fn register_items(&mut self, reg: &mut ContentBuilder) {
@@ -56,6 +61,18 @@ impl ContentMod for BaseMod {
             on_update: Some(FallingBlock::behaviour_on_update),
             ..Default::default()
         });
+        mut_shr!("chest").behavior = Some(box Behavior {
+            on_use: Some(super::chest_use),
+            ..Default::default()
+        });
+        mut_shr!("furnace").behavior = Some(box Behavior {
+            on_use: Some(super::chest_use),
+            ..Default::default()
+        });
+        mut_shr!("sign").behavior = Some(box Behavior {
+            on_use: Some(super::chest_use),
+            ..Default::default()
+        });
         /* mut_shr!("glowstone").behavior = Some(box Behavior {
             on_update: Some(rnd_glow_dec),
             ..Default::default()
@@ -79,6 +96,11 @@ impl ContentMod for BaseMod {
                 .collect();
             reg.crafting.register(true, input, ItemStack::of(reg.items.get(&shaped.output).cloned().unwrap(), 1));
         }
+        for smelting in saved.smelting {
+            let input = reg.items.get(&smelting.input).cloned().unwrap();
+            let output = ItemStack::of(reg.items.get(&smelting.output).cloned().unwrap(), smelting.count);
+            reg.crafting.register_smelting(input, output);
+        }
     }
 }
 
@@ -1,12 +1,13 @@
 
 use crate::util::fdiv;
-use crate::loader::Loader;
 use crate::crafting::CraftingRegistry;
 use crate::rustcraft::component::{Physics,Position,PlayerData,View};
+use crate::save::ModInfo;
 use crate::prelude::*;
 
 pub trait ContentMod {
     fn name(&mut self) -> &str;
+    fn version(&mut self) -> &str {"0.0.0"}
     //fn register_components(&mut self, cnt: &mut ContentBuilder) {}
     fn register_entities(&mut self, cnt: &mut ContentBuilder) {}
     fn register_behaviors(&mut self, cnt: &mut ContentBuilder) {}
@@ -15,6 +16,7 @@ pub trait ContentMod {
 }
 
 pub struct ContentBuilder {
+    pub mods: Vec<ModInfo>,
     pub items: HashMap<String, ItemLike>,
     pub crafting: CraftingRegistry,
     pub entities: EntityRegistry,
@@ -25,6 +27,7 @@ pub struct ContentBuilder {
 impl ContentBuilder {
     pub fn new() -> Self {
         Self {
+            mods: vec![],
             items: HashMap::new(),
             crafting: CraftingRegistry::new(),
             entities: EntityRegistry::new(),
@@ -33,19 +36,51 @@ impl ContentBuilder {
         }
     }
     pub fn load_mod(&mut self, cmod: &mut dyn ContentMod) {
+        let items_before = self.items.keys().cloned().collect::<HashSet<_>>();
+        let behaviors_before = self.behaviors.behaviors.keys().cloned().collect::<HashSet<_>>();
+        let recipes_before = self.crafting.recipes.len();
         //cmod.register_components(self);
         cmod.register_entities(self);
         cmod.register_behaviors(self);
         cmod.register_items(self);
         cmod.register_recipies(self);
+        // everything the mod added, as it would be serialized, so any change to it changes the hash.
+        // Blocks include their properties and state overrides, so a different set of states changes it too.
+        let mut items = self.items.iter()
+            .filter(|(id, _)| !items_before.contains(*id))
+            .map(|(id, item)| (id, match item {
+                ItemLike::Block(block) => bincode::serialize(block.as_ref()),
+                ItemLike::Item(item) => bincode::serialize(item.as_ref()),
+            }.unwrap_or_default()))
+            .collect::<Vec<_>>();
+        items.sort();
+        let mut behaviors = self.behaviors.behaviors.keys()
+            .filter(|name| !behaviors_before.contains(*name))
+            .collect::<Vec<_>>();
+        behaviors.sort();
+        let recipes = &self.crafting.recipes[recipes_before..];
+        let name = cmod.name().to_owned();
+        let version = cmod.version().to_owned();
+        let hash = util::stable_hash(&(&name, &version, items, behaviors, recipes));
+        self.mods.push(ModInfo {
+            name,
+            version,
+            hash,
+        });
     }
-    pub fn finish(self) -> Content {
-        Content {
-            blocks: self.items
-                .iter()
-                .filter_map(|(k, v)| v.as_block().map(|b| (k, b)))
-                .map(|(k, v)| (k.clone(), v.0.0.clone()))
-                .collect(),
+    pub fn finish(self) -> std::io::Result<Content> {
+        self.finish_with_ids(&BlockIdMap::default())
+    }
+    /// Finish with the block ids of a world, so its blocks keep their numbers.
+    /// Fails if the block states do not fit into the numeric ids.
+    pub fn finish_with_ids(self, known: &BlockIdMap) -> std::io::Result<Content> {
+        Ok(Content {
+            mods: self.mods,
+            blocks: BlockRegistry::new(
+                self.items.values().filter_map(ItemLike::as_block).map(|b| b.0.0.clone()),
+                known,
+                &self.behaviors
+            )?,
             items: ItemRegistry {
                 items: self.items,
             },
@@ -53,6 +88,6 @@ impl ContentBuilder {
             //entities: self.entities,
             //components: self.components,
             behaviors: self.behaviors,
-        }
+        })
     }
 }
\ No newline at end of file
@@ -14,6 +14,10 @@ pub struct PlayerGUI {
     pub selector: Texture,
     pub crosshairs: Texture,
     pub inventory: PlayerInventoryShell,
+    /// Shell for block inventories, pointed at the block when one is opened
+    pub chest: ChestGUI,
+    /// Shell for furnaces, pointed at the block like `chest`
+    pub furnace: FurnaceGUI,
     pub selected_slot: i32,
 }
 
@@ -24,6 +28,8 @@ impl PlayerGUI {
         let selector = Texture::from_path("assets/item_selected.png").into();
         let crosshairs = Texture::from_path("assets/crosshairs.png").into();
         let inventory = player_inventory();
+        let chest = ChestGUI::new();
+        let furnace = FurnaceGUI::new();
 
         /* let heart = std::rc::Rc::from(Texture::from_path("assets/heart.png"));
         let mut hearts = vec![];
@@ -40,7 +46,9 @@ impl PlayerGUI {
             hotbar,
             crosshairs,
             selector,
-            inventory
+            inventory,
+            chest,
+            furnace,
         }
 
     }
@@ -65,7 +73,7 @@ impl InventoryShell for PlayerHotbarShell {
     fn texture(&self) -> &Texture {&self.texture}
     fn slots(&self) -> &[PixelPos] {self.slots.as_ref()}
     fn borrow_data<'w>(&self, w: &'w mut WorldData) -> Option<&'w mut dyn InventoryData> {
-        let pdata: &mut PlayerData = w.entities.ecs.query_one_mut::<&mut PlayerData>(w.entities.player).ok()?;
+        let pdata: &mut PlayerData = w.entities.local_player_data()?;
         let d: &mut dyn InventoryData = &mut pdata.inventory.data as &mut dyn InventoryData;
         Some(d)
     }
@@ -82,7 +90,7 @@ impl InventoryShell for PlayerInventoryShell {
     fn texture(&self) -> &Texture {&self.texture}
     fn slots(&self) -> &[PixelPos] {self.slots.as_ref()}
     fn borrow_data<'w>(&self, w: &'w mut WorldData) -> Option<&'w mut dyn InventoryData> {
-        let pdata: &mut PlayerData = w.entities.ecs.query_one_mut::<&mut PlayerData>(w.entities.player).ok()?;
+        let pdata: &mut PlayerData = w.entities.local_player_data()?;
         let d: &mut dyn InventoryData = &mut pdata.inventory.data as &mut dyn InventoryData;
         Some(d)
     }
@@ -223,12 +231,40 @@ impl InventoryShell for ChestGUI {
     fn texture(&self) -> &Texture {&self.texture}
     fn slots(&self) -> &[PixelPos] {&self.slots}
     fn borrow_data<'w>(&self, w: &'w mut WorldData) -> Option<&'w mut dyn InventoryData> {
-        /* let block = w.blocks.block_at_mut_unguarded(&self.chest)?;
-        if block.id.as_ref() != "chest" {
-            return None
+        let slots = w.blocks.block_entity_mut(&self.chest)?.slots_mut()?;
+        Some(slots as &mut dyn InventoryData)
+    }
+}
+
+#[derive(Clone)]
+pub struct FurnaceGUI {
+    pub texture: Arc<Texture>,
+    /// Input, fuel and output, like the slots of `BlockEntity::Furnace`
+    pub slots: [PixelPos; 3],
+    pub furnace: BlockPos,
+}
+
+impl FurnaceGUI {
+    pub fn new() -> Self {
+        FurnaceGUI {
+            texture: Texture::from_path("assets/furnace.png").into(),
+            slots: [
+                (64,59).into(),
+                (64,19).into(),
+                (104,38).into(),
+            ],
+            furnace: BlockPos::zero()
         }
-        Some(block.mutate().data.as_mut().unwrap()) */
-        None
+    }
+}
+
+impl InventoryShell for FurnaceGUI {
+    fn dyn_clone(&self) -> Box<dyn InventoryShell> {box self.clone() as Box<dyn InventoryShell>}
+    fn texture(&self) -> &Texture {&self.texture}
+    fn slots(&self) -> &[PixelPos] {&self.slots}
+    fn borrow_data<'w>(&self, w: &'w mut WorldData) -> Option<&'w mut dyn InventoryData> {
+        let slots = w.blocks.block_entity_mut(&self.furnace)?.slots_mut()?;
+        Some(slots as &mut dyn InventoryData)
     }
 }
 
@@ -258,7 +294,7 @@ impl InventoryShell for CraftingGUI {
     fn slots(&self) -> &[PixelPos] {&self.slots}
     fn borrow_data<'w>(&self, w: &'w mut WorldData) -> Option<&'w mut dyn InventoryData> {
         compile_warning!(wrong);
-        let pdata: &mut PlayerData = w.entities.ecs.query_one_mut::<&mut PlayerData>(w.entities.player).ok()?;
+        let pdata: &mut PlayerData = w.entities.local_player_data()?;
         let d: &mut dyn InventoryData = &mut pdata.inventory.data as &mut dyn InventoryData;
         Some(d)
     }
@@ -1,14 +1,15 @@
+#[cfg(feature = "client")]
 pub mod inventory;
 pub mod base;
 pub mod builder;
 
-use crate::loader::Loader;
 use crate::crafting::CraftingRegistry;
+use crate::save::ModInfo;
 use crate::prelude::*;
 use component::{Physics, Position, PlayerData, View};
 
-pub fn make_player() -> ((Position, Physics, View, PlayerData), util::AABB) {
-    let pos = Position::new(Vector3 {x:50., y: 55., z: 50.}.as_coord(), (0.8,1.9,0.8).into());
+pub fn make_player(spawn: WorldPos) -> ((Position, Physics, View, PlayerData), util::AABB) {
+    let pos = Position::new(spawn, (0.8,1.9,0.8).into());
     let aabb = pos.get_aabb();
     let view = View::from(Vector3 {
         x: 0.5,
@@ -49,6 +50,8 @@ impl SerialItemRegistry {
 #[derive(serde::Deserialize)]
 struct SavedRecipies {
     shaped: Vec<SavedRecipe>,
+    #[serde(default)]
+    smelting: Vec<SavedSmelting>,
 }
 
 #[derive(serde::Deserialize)]
@@ -59,14 +62,25 @@ struct SavedRecipe {
     count: usize
 }
 
+#[derive(serde::Deserialize)]
+struct SavedSmelting {
+    input: String,
+    output: String,
+    #[serde(default = "one")]
+    count: usize
+}
+
 fn chest_use(pos: BlockPos, data: &mut WorldData) {
-    // TODO somehow trigger inventory to open
+    if data.blocks.block_entity(&pos).is_some() {
+        data.open_inventory(pos);
+    }
 }
 
 const fn one() -> usize {1}
 
 pub struct Content {
-    pub blocks: HashMap<String, BlockData>,
+    pub mods: Vec<ModInfo>,
+    pub blocks: BlockRegistry,
     pub items: ItemRegistry,
     pub crafting: CraftingRegistry,
     // pub entities: EntityRegistry,
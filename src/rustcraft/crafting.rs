@@ -60,10 +60,23 @@ impl Node {
     }
 }
 
+/// A registered recipe by item ids, to tell content apart
+#[derive(Debug, serde::Serialize)]
+pub struct RecipeDesc {
+    pub kind: &'static str,
+    pub input: Vec<Option<String>>,
+    pub output: String,
+    pub count: usize,
+}
+
 #[derive(Debug)]
 pub struct CraftingRegistry {
     shaped: Vec<Node>,
     unshaped: Vec<Node>,
+    /// Furnace output by input item
+    smelting: HashMap<ItemLike, ItemStack>,
+    /// Every recipe in the order it was registered
+    pub recipes: Vec<RecipeDesc>,
 }
 
 impl CraftingRegistry {
@@ -72,10 +85,18 @@ impl CraftingRegistry {
         Self {
             shaped: vec![Node::Branch(HashMap::new(), None)],
             unshaped: vec![Node::Branch(HashMap::new(), None)],
+            smelting: HashMap::new(),
+            recipes: vec![],
         }
     }
 
     pub fn register(&mut self, shaped: bool, mut input: Vec<Option<ItemLike>>, output: ItemStack) {
+        self.recipes.push(RecipeDesc {
+            kind: if shaped {"shaped"} else {"unshaped"},
+            input: input.iter().map(|i| i.as_ref().map(|i| i.id().to_owned())).collect(),
+            output: output.item.id().to_owned(),
+            count: output.count,
+        });
         if shaped {
             self.shaped[0].register(&input, output);
         } else {
@@ -89,4 +110,21 @@ impl CraftingRegistry {
         self.shaped[0].search(input)
     }
 
+    pub fn register_smelting(&mut self, input: ItemLike, output: ItemStack) {
+        self.recipes.push(RecipeDesc {
+            kind: "smelting",
+            input: vec![Some(input.id().to_owned())],
+            output: output.item.id().to_owned(),
+            count: output.count,
+        });
+        if self.smelting.insert(input, output).is_some() {
+            println!("Smelting recipe overridden");
+        }
+    }
+
+    /// What a furnace makes out of one `input`
+    pub fn smelt(&self, input: &ItemLike) -> Option<&ItemStack> {
+        self.smelting.get(input)
+    }
+
 }
@@ -1,5 +1,4 @@
 
-use game::world::updates::Updates;
 use crate::prelude::*;
 use super::*;
 
@@ -15,36 +14,15 @@ impl<'cnt: 'b, 'b> GameLoop<'cnt> {
 
             let start = Instant::now();
             
-            Updates::update(&mut self.world);
-            self.world.ticks += 1;
+            // blocks and entities are updated by the server, which sends us the changes
+            self.world.advance_tick();
+            self.sync_player();
 
-            component::ItemCmp::system_tick_age_items(&mut self.world);
-            component::PathFinding::system_update_tick(&mut self.world);
-    
-            let mut rng = rand::thread_rng();
-            use rand::prelude::*;
-    
-            let keys = self.world.blocks.chunks.iter()
-                .filter(|(_,c)|
-                    c.all_neighbours_loaded() &&
-                    c.chunk.renderable()
-                )
-                .map(|(k,_)| k.clone())
-                .collect::<Vec<_>>();
-            for cp in keys {
-                // println!("{:?}",cp);
-                for _ in 0..consts::RANDOM_TICK_SPEED {
-                    let random = rng.gen::<(i32,i32,i32)>();
-                    let pos = cp.as_block() + Vector3::from(random).map(|x| x.abs() % 16).into();
-                    if let Some(on_rnd_tick) = self.world.blocks.block_at(&pos).and_then(|b| b.behavior.as_ref()).and_then(|beh| beh.on_rnd_tick.as_ref()) {
-                        on_rnd_tick(pos, &mut self.world)
-                    }
-                }
+            if !self.unsent_inputs.is_empty() {
+                let inputs = std::mem::take(&mut self.unsent_inputs);
+                self.send(server::ClientMsg::Inputs(inputs));
             }
 
-            // tmp load around player every tick
-            // self.tx.send(ClientMsg::LoadAround(self.player_pos.pos.as_chunk()));
-
             Some(start.elapsed())
         } else {
             None
@@ -4,7 +4,6 @@ use super::*;
 
 #[derive(Default)]
 pub struct Return {
-    pub do_chunk_load: bool,
     pub do_quit: bool,
 }
 
@@ -12,6 +11,9 @@ impl<'cnt: 'b, 'b> GameLoop<'cnt> {
     pub fn handle_input(&'b mut self) -> Return {
 
         let mut ret = Return::default();
+        // cursor stack of an inventory closed this frame
+        let mut put_back = None;
+        let mut command = None;
 
         self.data.input.start_new_frame();
         self.data.display.video.text_input().start();
@@ -25,7 +27,7 @@ impl<'cnt: 'b, 'b> GameLoop<'cnt> {
                 TextInput { ref text, .. } => {
                     let input_text = text;
                     match &mut self.state {
-                        GameState::Chat { text, start_frame } if *start_frame != self.rdata.frame_time => {
+                        GameState::Chat { text, start_frame, .. } if *start_frame != self.rdata.frame_time => {
                             let mut txt = text.text().to_owned();
                             txt.push_str(&input_text);
                             text.set_text(txt);
@@ -35,6 +37,9 @@ impl<'cnt: 'b, 'b> GameLoop<'cnt> {
                 },
                 Quit {..} => ret.do_quit = true,
                 KeyDown {keycode: Some(Escape), repeat: false, ..} => {
+                    if let GameState::Inventory { picked_item, .. } = &mut self.state {
+                        put_back = Some(picked_item.take());
+                    }
                     self.state = match self.state {
                         GameState::Paused => {
                             self.data.display.set_mouse_capture(true);
@@ -52,7 +57,6 @@ impl<'cnt: 'b, 'b> GameLoop<'cnt> {
                 },
                 KeyDown {keycode: Some(F5), repeat: false, .. } => self.data.settings.third_person ^= true,
                 KeyDown {keycode: Some(F11), repeat: false, ..} => self.data.display.set_fullscreen(!self.data.display.state.fullscreen),
-                KeyDown {keycode: Some(R), ..} => ret.do_chunk_load = true,
                 KeyDown {keycode: Some(E), repeat: false, ..} => {
                     use crate::rustcraft::inventory::InventoryShell;
                     match self.state {
@@ -61,9 +65,11 @@ impl<'cnt: 'b, 'b> GameLoop<'cnt> {
                             self.state = GameState::Inventory {
                                 picked_item: Option::None,
                                 inventory: self.pgui.inventory.dyn_clone(),
+                                aux_inventory: Option::None,
                             }
                         },
-                        GameState::Inventory { .. } => {
+                        GameState::Inventory { ref mut picked_item, .. } => {
+                            put_back = Some(picked_item.take());
                             self.data.display.set_mouse_capture(true);
                             self.state = GameState::Playing { breaking: std::option::Option::None }
                         },
@@ -72,12 +78,8 @@ impl<'cnt: 'b, 'b> GameLoop<'cnt> {
                 },
                 KeyDown {keycode: Some(Return), repeat: false, ..} => {
                     match &self.state {
-                        GameState::Chat { text, .. } => {
-                            let cmd: Option<Cmd> = text.text().parse().ok();
-                            println!("{}\n => {:?}",text.text(),cmd);
-                            if let Some(cmd) = cmd {
-                                cmd.exec(&mut self.world, &self.idata);
-                            }
+                        GameState::Chat { text, sign, .. } => {
+                            command = Some((*sign, text.text().to_owned()));
                             self.data.display.set_mouse_capture(true);
                             self.state = GameState::Playing { breaking: std::option::Option::None }
                         },
@@ -88,14 +90,14 @@ impl<'cnt: 'b, 'b> GameLoop<'cnt> {
                     match self.state {
                         GameState::Playing {..} => {
                             self.data.display.set_mouse_capture(false);
-                            self.state = GameState::Chat { text: self.idata.font.build_text("".into()), start_frame: self.rdata.frame_time }
+                            self.state = GameState::Chat { text: self.idata.font.build_text("".into()), start_frame: self.rdata.frame_time, sign: std::option::Option::None }
                         },
                         _ => {}
                     };
                 },
                 KeyDown {keycode: Some(Backspace), ..} => {
                     match &mut self.state {
-                        GameState::Chat { ref mut text, start_frame } => {
+                        GameState::Chat { ref mut text, .. } => {
                             let mut txt = text.text().to_owned();
                             txt.pop();
                             text.set_text(txt);
@@ -110,8 +112,26 @@ impl<'cnt: 'b, 'b> GameLoop<'cnt> {
 
         self.pgui.scroll(-self.data.input.scroll());
 
+        if let Some(stack) = put_back {
+            self.close_inventory(stack);
+        }
+        // commands run on the server, for this player
+        match command {
+            Some((Some(pos), text)) => self.send(server::ClientMsg::SetSignText(pos, text)),
+            Some((None, line)) => self.send(server::ClientMsg::Command(line)),
+            None => {}
+        }
+
         ret
 
     }
 
+    /// Put the stack held by the cursor back into the player's inventory
+    pub fn close_inventory(&mut self, mut picked_item: Option<ItemStack>) {
+        if let Some(pdata) = self.world.entities.local_player_data() {
+            pdata.inventory.merge(&mut picked_item);
+        }
+        self.send(server::ClientMsg::CloseInventory);
+    }
+
 }
\ No newline at end of file
@@ -9,124 +9,67 @@ use crate::game_loop::Text;
 use crate::game_loop::GameState;
 use crate::prelude::*;
 use world::updates::Updates;
+use server::ClientMsg;
 
 impl<'cnt: 'b, 'b> GameLoop<'cnt> {
     pub fn handle_interaction(&'b mut self, ret: Return) -> Option<RayCastHit> {
 
         let mut raycast_hit = None;
-        let mut on_use: std::option::Option<(coords::WorldPos, for<'r, 's> fn(coords::BlockPos, &'r mut rustcraft::world::WorldData<'s>))> = None;
-        let mut to_spawn: Vec<((Position, Physics, ItemCmp, Model), util::AABB)> = vec![];
+        let mut msgs = vec![];
 
-        let mut opos: Option<WorldPos> = None;
-
-        /* if let Ok((pos, phys, view, pdata)) = self.world.entities.ecs.query_one_mut::<(&mut Position, &mut Physics, &View, &mut PlayerData)>(self.world.entities.player) {
+        if let GameState::Playing{ breaking } = &mut self.state {
 
-            raycast_hit = self.world.blocks.raycast(pos.pos+view.offset().into(), &pos.heading(), 5.);
-            opos = Some(pos.pos);
+            raycast_hit = self.world.blocks.raycast(self.player_pos.pos + self.player_view.offset().into(), &self.player_pos.heading(), consts::REACH_DISTANCE);
 
             let b = &self.world.blocks;
             self.debug_text.set_data(
-                &pos.pos,
+                &self.player_pos.pos,
                 raycast_hit.and_then(|hit| b.block_at(&hit.hit).map(|b| (&b.name, hit.hit.as_block()))),
                 self.rdata.delta,
                 self.last_tick_dur
             );
 
-            
-            
-        } */
-        
-        if let GameState::Playing{ breaking } = &mut self.state {
-
-            self.player_phys.set_edge_stop(self.data.input.holding_sneak());
-
-            if self.data.input.holding_jump() {
-                self.player_phys.try_jump(self.rdata.delta);
-            }
-
             if !self.data.input.holding_primary() || raycast_hit.is_none() {
-                *breaking = None;
+                if breaking.take().is_some() {
+                    msgs.push(ClientMsg::DigCancel);
+                }
             }
             if self.data.input.holding_primary() {
-                if let Some(RayCastHit {hit, prev: hit_prev, ..}) = raycast_hit {
+                if let Some(RayCastHit {hit, ..}) = raycast_hit {
+                    let target = hit.as_block();
 
-                    if let Some(breaking) = breaking {
-                        if breaking.1 != hit.as_block() {
-                            *breaking = (0., hit.as_block());
-                        }
-                    } else {
-                        *breaking = Some((0., hit.as_block()));
+                    if breaking.map_or(true, |(_, pos)| pos != target) {
+                        *breaking = Some((0., target));
+                        msgs.push(ClientMsg::DigStart(target));
                     }
-                    
-                    let block = self.world.blocks.block_at(&hit).unwrap().clone();
-
-                    let broken = {
-                        let t = {
-                            let breaking = breaking.as_mut().unwrap();
-                            breaking.0 += self.rdata.delta; // TODO mult by hardness factor
-                            breaking.0
-                        };
-                        if t >= 1.0 {
-                            *breaking = None;
-                            true
-                        } else {
-                            false
-                        }
+
+                    // the server breaks the block and sends the change back
+                    let t = {
+                        let breaking = breaking.as_mut().unwrap();
+                        breaking.0 += self.rdata.delta;
+                        breaking.0
                     };
-                    
-                    if broken {
-                        if self.world.blocks.set_block_at(&hit, self.idata.air()) {
-                            if let Some(drop_id) = &block.drops {
-                                let mut stack = ItemStack::of(self.idata.content.items.get(&drop_id).clone(), 1);
-                                let phys = Physics::new();
-                                let pos = Position::new(hit.align_center(),(0.3,0.3,0.3).into());
-                                let aabb = pos.get_aabb();
-                                let model = box util::RenderedItem {
-                                    vao: self.idata.item_cubes.clone(),
-                                    offset: self.invren.iren.offsets[drop_id]
-                                };
-                                let cmps = (
-                                    pos,
-                                    phys,
-                                    ItemCmp::from(stack),
-                                    Model::from(model as Box<dyn Drawable>),
-                                );
-                                to_spawn.push((cmps,aabb));
-                            }
-                            self.world.block_updates.add_area(hit.as_block());
-                        }
+                    let break_ticks = self.world.blocks.block_at(&target).map_or(0, BlockData::break_ticks);
+                    if t >= break_ticks as f32 * consts::TICK_DURATION.as_secs_f32() {
+                        *breaking = None;
+                        msgs.push(ClientMsg::DigFinish(target));
                     }
                 }
             } else if self.data.input.clicked_secondary() {
-
-                /* if let Some(RayCastHit {hit, prev: hit_prev, ..}) = raycast_hit {
-                    let hit_prev = hit_prev.as_block();
-                    let maybe_item = &mut pdata.inventory.data[self.pgui.selected_slot as usize];
-                    let mut success = false;
-                    if let Some(ref mut block) = maybe_item.as_mut().and_then(|item| item.item.as_block()) {
-                        if self.world.blocks.set_block_at(&hit_prev, &block) {
-                            success = true;
-                            self.world.block_updates.add_area(hit_prev);
-                            self.world.block_updates.add_single(hit_prev);
-                        }
-                    } else {
-                        let b = self.world.blocks.block_at(&hit).unwrap();
-                        on_use = b.behavior.as_ref().and_then(|b| b.on_use.map(|f| (hit,f)));
-                    }
-                    if success {
-                        ItemStack::deduct(maybe_item, 1);
-                    }
-                } */
-
+                if let Some(RayCastHit {hit, prev: hit_prev, ..}) = raycast_hit {
+                    msgs.push(ClientMsg::Place {
+                        pos: hit_prev.as_block(),
+                        against: hit.as_block(),
+                        slot: self.pgui.selected_slot(),
+                    });
+                }
             }
 
+            // movement is applied by `predict_movement`
             self.player_pos.rotate(
                 self.data.input.mouse_y() as f32 * self.data.settings.mouse_sensitivity,
                 self.data.input.mouse_x() as f32 * self.data.settings.mouse_sensitivity
             );
-
-            let force = self.data.input.compute_movement_vector(self.player_pos.yaw()) * 40.;
-            self.player_phys.apply_force_continuous(self.rdata.delta, &force);
         }
 
         self.rdata.view_mat = self.player_view.calc_view_mat(&self.player_pos);
@@ -141,42 +84,46 @@ impl<'cnt: 'b, 'b> GameLoop<'cnt> {
             self.rdata.view_mat = self.rdata.view_mat * trans;
         }
 
-        for (cmps, aabb) in to_spawn {
-            let ent = self.world.entities.ecs.spawn(cmps);
-            self.world.entities.tree.insert(ent, ent, &aabb);
-        }
-
-        if let Some((p,f)) = on_use {
-            f(p.as_block(), &mut self.world);
-        }
-
-        if let Some(pos) = opos {
-            if ret.do_chunk_load {
-                /* worker.send(WorkerJob::SaveChunk(
-                    self.data.self.world.take_chunk()
-                )); */
-                self.world.load_around(&pos);
-            }
+        for msg in msgs {
+            self.send(msg);
         }
 
         // interact with inventory
+        let mut clicked_slot = None;
         if self.data.input.clicked_primary() {
             use inventory::*;
             match self.state {
-                GameState::Inventory { ref mut picked_item, .. } => {
+                GameState::Inventory { ref mut picked_item, ref aux_inventory, .. } => {
                     let mpos = self.data.input.mouse_pos(self.data.display.size_i32().1);
+                    let (dx, aux_dx) = match aux_inventory {
+                        Some(aux) => InventoryRenderer::side_by_side(&self.pgui.inventory, aux.as_ref()),
+                        None => (0, 0)
+                    };
                     // compile_warning!(need corner anchor for determining hovered slot);
                     // ! hot fix
-                    if let Some(hovered_slot) = self.invren.corner_cursor(&self.pgui.inventory, mpos) {
+                    if let Some(hovered_slot) = self.invren.corner_cursor(&self.pgui.inventory, mpos, dx) {
                         if let Some(inv_data) = self.pgui.inventory.borrow_data(&mut self.world) {
                             ItemStack::transfer_or_swap(picked_item, inv_data.slot_mut(hovered_slot));
                         }
+                        clicked_slot = Some(ClientMsg::ClickSlot(hovered_slot));
+                    } else if let Some(aux) = aux_inventory {
+                        // the server does the same with the block's inventory and sends it back
+                        if let Some(hovered_slot) = self.invren.corner_cursor(aux.as_ref(), mpos, aux_dx) {
+                            if let Some(inv_data) = aux.borrow_data(&mut self.world) {
+                                ItemStack::transfer_or_swap(picked_item, inv_data.slot_mut(hovered_slot));
+                            }
+                            clicked_slot = Some(ClientMsg::ClickContainerSlot(hovered_slot));
+                        }
                     }
                 },
                 _ => {}
             }
         }
 
+        if let Some(msg) = clicked_slot {
+            self.send(msg);
+        }
+
         raycast_hit
 
     }
@@ -0,0 +1,185 @@
+use server::{ClientMsg, ServerMsg};
+use crate::save::SavedChunk;
+use crate::prelude::*;
+use super::*;
+
+impl<'cnt: 'b, 'b> GameLoop<'cnt> {
+
+    /// Apply the messages received from the server, returns false once it is gone
+    pub fn handle_msgs(&'b mut self) -> bool {
+        loop {
+            let msg = match self.conn.try_recv() {
+                Ok(Some(msg)) => msg,
+                Ok(None) => break,
+                Err(e) => {
+                    println!("Lost connection to server: {}", e);
+                    return false
+                }
+            };
+            match msg {
+                ServerMsg::Spawn { pos, ticks, day_time } => {
+                    self.player_pos.pos = pos;
+                    self.pending_inputs.clear();
+                    self.world.ticks = ticks;
+                    self.world.day_time = day_time;
+                    self.spawned = true;
+                },
+                ServerMsg::ChunkData(pos, bytes) => match server::decode_chunk(&bytes) {
+                    Ok(saved) => self.receive_chunk(&saved),
+                    Err(e) => println!("Received broken chunk {:?}: {}", pos, e),
+                },
+                ServerMsg::UnloadChunk(pos) => {
+                    self.world.blocks.remove_chunk(pos);
+                },
+                ServerMsg::BlockChanged(pos, id) => self.set_block(pos, id),
+                ServerMsg::MultiBlockChange(_, blocks) => {
+                    for (pos, id) in blocks {
+                        self.set_block(pos, id);
+                    }
+                },
+                ServerMsg::EntitySpawn { id, pos, vel, model } => {
+                    let interp = Interpolation::new(pos.pos, vel);
+                    let ent = match model {
+                        Some(model) => self.world.entities.ecs.spawn((id, pos, interp, model)),
+                        None => self.world.entities.ecs.spawn((id, pos, interp)),
+                    };
+                    if let Some(old) = self.net_entities.insert(id, ent) {
+                        let _ = self.world.entities.ecs.despawn(old);
+                    }
+                },
+                ServerMsg::EntityDespawn(id) => {
+                    if let Some(ent) = self.net_entities.remove(&id) {
+                        let _ = self.world.entities.ecs.despawn(ent);
+                    }
+                },
+                ServerMsg::EntityMoves(moves) => {
+                    for m in moves {
+                        let ent = match self.net_entities.get(&m.id) {
+                            Some(ent) => *ent,
+                            None => continue
+                        };
+                        if let Ok((pos, interp)) = self.world.entities.ecs.query_one_mut::<(&Position, &mut Interpolation)>(ent) {
+                            interp.retarget(pos.pos, m.pos, m.vel);
+                        }
+                    }
+                },
+                ServerMsg::PlayerState { seq, pos, phys } => self.reconcile(seq, pos, phys),
+                ServerMsg::Inventory(stacks) => {
+                    let items = &self.idata.content.items;
+                    if let Some(pdata) = self.world.entities.local_player_data() {
+                        for (slot, saved) in pdata.inventory.data.iter_mut().zip(stacks) {
+                            *slot = saved.and_then(|saved| ItemStack::from_saved(&saved, items));
+                        }
+                    }
+                },
+                ServerMsg::BlockEntity(pos, saved) => {
+                    let items = &self.idata.content.items;
+                    if let Some(c) = self.world.blocks.chunk_at_mut(pos.as_chunk()) {
+                        c.block_entities.insert(pos, BlockEntity::from_saved(&saved, items));
+                    }
+                },
+                ServerMsg::OpenInventory(pos) => {
+                    use crate::rustcraft::inventory::InventoryShell;
+                    // the server still has the stack on its cursor as well
+                    let picked_item = match &mut self.state {
+                        GameState::Inventory { picked_item, .. } => picked_item.take(),
+                        _ => None
+                    };
+                    let aux_inventory: Box<dyn InventoryShell> = match self.world.blocks.block_entity(&pos) {
+                        Some(BlockEntity::Furnace { .. }) => {
+                            let mut furnace = self.pgui.furnace.clone();
+                            furnace.furnace = pos;
+                            box furnace
+                        }
+                        _ => {
+                            let mut chest = self.pgui.chest.clone();
+                            chest.chest = pos;
+                            box chest
+                        }
+                    };
+                    self.data.display.set_mouse_capture(false);
+                    self.state = GameState::Inventory {
+                        picked_item,
+                        inventory: self.pgui.inventory.dyn_clone(),
+                        aux_inventory: Some(aux_inventory),
+                    };
+                },
+                ServerMsg::CloseInventory => {
+                    if let GameState::Inventory { picked_item, .. } = &mut self.state {
+                        let picked_item = picked_item.take();
+                        self.close_inventory(picked_item);
+                        self.data.display.set_mouse_capture(true);
+                        self.state = GameState::Playing { breaking: None };
+                    }
+                },
+                ServerMsg::CommandOutput(output) => {
+                    println!("{}", output);
+                    self.command_output = Some((self.idata.font.build_text(output), Instant::now()));
+                },
+                ServerMsg::EditSign(pos) => {
+                    if self.state.is_playing() {
+                        let text = match self.world.blocks.block_entity(&pos) {
+                            Some(BlockEntity::Sign(text)) => text.clone(),
+                            _ => String::new()
+                        };
+                        self.data.display.set_mouse_capture(false);
+                        self.state = GameState::Chat {
+                            text: self.idata.font.build_text(text),
+                            start_frame: self.rdata.frame_time,
+                            sign: Some(pos),
+                        };
+                    }
+                },
+            }
+        }
+        // block changes are only replicated from the server, and so are the items block entities spill
+        self.world.blocks.changed_blocks.clear();
+        self.world.blocks.dropped_block_entities.clear();
+        true
+    }
+
+    /// Add a chunk sent by the server. Chunks are rendered once all their neighbours have arrived.
+    fn receive_chunk(&mut self, saved: &SavedChunk) {
+        let idata = self.idata;
+        let mut chunk = match Chunk::from_saved(saved, &idata.content) {
+            Ok(chunk) => Box::new(chunk),
+            Err(e) => {
+                println!("Invalid chunk {:?} from server: {}", saved.pos, e);
+                return
+            }
+        };
+        chunk.chunk_state = ChunkState::Detailed;
+        self.world.blocks.insert_chunk(chunk);
+        let pos = saved.pos;
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let p = (pos.x+dx, pos.y+dy, pos.z+dz).into();
+                    if let Some(c) = self.world.blocks.chunks.get_mut(&p) {
+                        if c.all_neighbours_loaded() && c.chunk.chunk_state == ChunkState::Detailed {
+                            c.chunk.chunk_state = ChunkState::Rendered;
+                            c.chunk.needs_refresh = true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn set_block(&mut self, pos: BlockPos, id: BlockId) {
+        let idata = self.idata;
+        match idata.content.blocks.by_id(id) {
+            Some(block) => {
+                self.world.blocks.set_block_at(&pos, block);
+            },
+            None => println!("Unknown block {} at {:?}", id, pos),
+        }
+    }
+
+    pub fn send(&mut self, msg: ClientMsg) {
+        if let Err(e) = self.conn.send(msg) {
+            println!("Failed to send to server: {}", e);
+        }
+    }
+
+}
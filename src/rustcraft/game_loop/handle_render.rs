@@ -41,7 +41,7 @@ impl<'b: 'cnt, 'cnt> GameLoop<'cnt> {
         self.chunk_renderer.load_view(&self.rdata.view_mat);
         let world_light = self.world.smooth_light_level().max(consts::MIN_BRIGHTNESS);
         self.chunk_renderer.load_glob_light(light_factor);
-        self.chunk_renderer.render(&mut self.world);
+        self.chunk_renderer.render();
 
         unsafe {
             gl::Disable(gl::BLEND);
@@ -172,16 +172,25 @@ impl<'b: 'cnt, 'cnt> GameLoop<'cnt> {
             self.invren.render_bottom(&self.pgui, &d, mpos);
         }
         match self.state {
-            GameState::Inventory { ref picked_item, .. } => {
+            GameState::Inventory { ref picked_item, ref aux_inventory, .. } => {
+                let (dx, aux_dx) = match aux_inventory {
+                    Some(aux) => InventoryRenderer::side_by_side(&self.pgui.inventory, aux.as_ref()),
+                    None => (0, 0)
+                };
                 if let Some(d) = self.pgui.inventory.borrow_data(&mut self.world) {
                     { 
-                        self.invren.render_centered(&self.pgui.inventory, &d, mpos);
+                        self.invren.render_centered(&self.pgui.inventory, &d, mpos, dx);
                         if let Some(item) = picked_item.as_ref().map(|s| &s.item) {
                             self.invren.render_floating_item(item, mpos);
                         }
                         
                     }
                 }
+                if let Some(aux) = aux_inventory {
+                    if let Some(d) = aux.borrow_data(&mut self.world) {
+                        self.invren.render_centered(aux.as_ref(), &d, mpos, aux_dx);
+                    }
+                }
                 if let Some(picked_item) = picked_item {
                     self.invren.render_floating_item(&picked_item.item, mpos);
                 }
@@ -216,6 +225,12 @@ impl<'b: 'cnt, 'cnt> GameLoop<'cnt> {
             _ => {}
         };
 
+        if let Some((output, received)) = &self.command_output {
+            if received.elapsed() < consts::COMMAND_OUTPUT_DURATION {
+                self.text_rend.render(output, -0.9, -0.8, self.data.display.size())
+            }
+        }
+
         self.text_rend.render(&self.debug_text.text, -0.9, 0.9, self.data.display.size());
         
         unsafe {
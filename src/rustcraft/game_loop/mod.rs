@@ -17,12 +17,15 @@ use std::time::{Instant, Duration};
 use crate::texture::Texture;
 use game::player::inventory::PlayerInventory;
 
+use crate::save::LevelData;
 use crate::prelude::*;
 
 mod handle_input;
 mod handle_game_tick;
 mod handle_render;
 mod handle_interaction;
+mod handle_msgs;
+mod prediction;
 mod state;
 use state::*;
 
@@ -36,23 +39,36 @@ pub struct GameLoop<'cnt> {
     pub last_tick: Instant,
     pub text_rend: TextRenderer,
     pub debug_text: DebugText,
+    /// Output of the last command the player ran, with when it arrived
+    pub command_output: Option<(Text, Instant)>,
     pub lines: LineProgram,
     pub prg: Program,
     pub sprg: StaticProgram,
     pub state: GameState,
     pub last_tick_dur: f32,
     pub invren: InventoryRenderer,
-    pub tx: mpsc::Sender<server::ClientMsg>,
-    pub rx: mpsc::Receiver<server::ServerMsg>,
+    pub conn: Box<dyn server::Connection<server::ClientMsg, server::ServerMsg>>,
     pub player_pos: Position,
     pub player_phys: Physics,
     pub player_view: View,
+    /// Whether the server has told us where the player is
+    pub spawned: bool,
+    /// Local entities replicating the server's
+    pub net_entities: HashMap<NetId, hecs::Entity>,
+    pub next_input: u32,
+    /// Inputs not yet sent to the server
+    pub unsent_inputs: Vec<PlayerInput>,
+    /// Inputs the server has not simulated yet, with the position predicted after each
+    pub pending_inputs: VecDeque<(PlayerInput, WorldPos)>,
 }
 
 impl<'cnt: 'b, 'b> GameLoop<'cnt> {
-    pub fn new(conn: (mpsc::Sender<server::ClientMsg>, mpsc::Receiver<server::ServerMsg>), data: &'cnt mut data::Data, rdata: &'cnt mut data::RData, idata: &'cnt data::IData) -> Self {
+    pub fn new(conn: Box<dyn server::Connection<server::ClientMsg, server::ServerMsg>>, data: &'cnt mut data::Data, rdata: &'cnt mut data::RData, idata: &'cnt data::IData) -> Self {
             
-        let mut world = WorldData::new(consts::DEBUG_SEED, idata.air());
+        // filled with the chunks the server sends
+        let mut world = WorldData::new(LevelData::new(consts::DEBUG_SEED, &idata.content), &idata.content.blocks);
+        let player = world.entities.spawn_player(world.level.spawn);
+        world.entities.player = Some(player);
 
         data.display.refresh();
         data.display.set_mouse_capture(true);
@@ -71,10 +87,6 @@ impl<'cnt: 'b, 'b> GameLoop<'cnt> {
 
         let chunk_renderer = ChunkRenderer::new();
         let mut pgui = PlayerGUI::new();
-        /* let worker_data = WorkerData {
-            registry: idata.registry.clone()
-        };
-        let mut worker = JobDispatcher::new(worker_data); */
         let mut last_tick = Instant::now();
         let text_rend = crate::engine::text::font::TextRenderer::new();
         let mut debug_text = DebugText::from(&idata.font);
@@ -97,13 +109,9 @@ impl<'cnt: 'b, 'b> GameLoop<'cnt> {
             highlight: Texture::from_path("assets/slot_highlight.png").into()
         };
 
-        world.load_around(&WorldPos::from(Vector3 {x:50., y: 55., z: 50.}));
-
-        let (tx,rx) = conn;
-        let (player_pos, player_phys, player_view,_) = make_player().0;
+        let (player_pos, player_phys, player_view,_) = make_player(world.level.spawn).0;
         Self {
-            tx,
-            rx,
+            conn,
             data,
             rdata,
             idata,
@@ -113,6 +121,7 @@ impl<'cnt: 'b, 'b> GameLoop<'cnt> {
             last_tick,
             text_rend,
             debug_text,
+            command_output: None,
             lines,
             prg,
             sprg,
@@ -122,14 +131,29 @@ impl<'cnt: 'b, 'b> GameLoop<'cnt> {
             player_pos,
             player_phys,
             player_view,
+            spawned: false,
+            net_entities: HashMap::new(),
+            next_input: 0,
+            unsent_inputs: vec![],
+            pending_inputs: VecDeque::new(),
         }
 
     }
 
     pub fn run_loop(&'b mut self) {while self.run() {}}
+
+    /// Copy the client side player position into the world's player entity
+    pub fn sync_player(&mut self) {
+        if let Some(player) = self.world.entities.player {
+            if let Ok(pos) = self.world.entities.ecs.query_one_mut::<&mut Position>(player) {
+                *pos = self.player_pos.clone();
+            }
+        }
+    }
+
     pub fn run(&'b mut self) -> bool {
 
-        self.rdata.delta = self.rdata.frame_time.elapsed().as_secs_f32().min(0.1);
+        self.rdata.delta = self.rdata.frame_time.elapsed().as_secs_f32().min(consts::MAX_FRAME_DELTA);
         self.rdata.frame_time = Instant::now();
 
         // GAME TICK
@@ -137,6 +161,8 @@ impl<'cnt: 'b, 'b> GameLoop<'cnt> {
             self.last_tick_dur = dur.as_secs_f32() * 1000.;
         }
 
+        if !self.handle_msgs() {return false}
+
         // INPUT PROCESSING
         let ret = self.handle_input();
         if ret.do_quit {return false}
@@ -145,18 +171,20 @@ impl<'cnt: 'b, 'b> GameLoop<'cnt> {
         
         // ! START SYSTEMS
         if !self.state.is_paused() {
-            WanderingAI::system_update(&mut self.world, self.rdata.delta);
-            Physics::system_update(&mut self.world, self.rdata.delta);
-            FallingBlock::system_collide_land(&mut self.world);
+            // other entities are simulated by the server
+            Interpolation::system_update(&mut self.world, self.rdata.delta);
+            Model::system_materialize(&mut self.world, &self.idata.item_cubes, &self.invren.iren.offsets);
 
-            self.player_phys.update(&mut self.player_pos, self.rdata.delta, &self.world.blocks);
+            // wait for the terrain below the player to arrive
+            if self.spawned && self.world.blocks.chunk_at(self.player_pos.pos.as_chunk()).is_some() {
+                self.predict_movement();
+            }
 
         }
         // ! STOP SYSTEMS
 
         // TODO this is too slow
-        self.world.blocks.refresh(&self.idata.content.items, &self.idata.atlas);
-        self.world.load(&self.idata.content, &self.idata.atlas, 5); // ! adjust for performance
+        self.chunk_renderer.update(&mut self.world.blocks, &self.idata.atlas);
 
         // RENDER
         let now = Instant::now();
@@ -0,0 +1,55 @@
+use crate::prelude::*;
+use super::*;
+
+impl<'cnt: 'b, 'b> GameLoop<'cnt> {
+
+    /// Move the player by this frame's input ahead of the server, keeping the input to send and replay it
+    pub fn predict_movement(&mut self) {
+        let playing = matches!(self.state, GameState::Playing {..});
+        let input = PlayerInput {
+            seq: self.next_input,
+            delta: self.rdata.delta,
+            pitch: self.player_pos.pitch().0,
+            yaw: self.player_pos.yaw().0,
+            movement: if playing {
+                self.data.input.compute_movement_vector(self.player_pos.yaw())
+            } else {
+                Vector3::zero()
+            },
+            jump: playing && self.data.input.holding_jump(),
+            sneak: playing && self.data.input.holding_sneak(),
+        };
+        self.next_input = self.next_input.wrapping_add(1);
+        input.apply(&mut self.player_pos, &mut self.player_phys, &self.world.blocks);
+        self.unsent_inputs.push(input.clone());
+        self.pending_inputs.push_back((input, self.player_pos.pos));
+    }
+
+    /// Compare the server's result for input `seq` with what we predicted,
+    /// rewinding to the server's state and replaying the newer inputs if they differ
+    pub fn reconcile(&mut self, seq: u32, pos: WorldPos, phys: Physics) {
+        // the server is done with everything up to `seq`, compared so the numbers may wrap
+        while let Some((input, _)) = self.pending_inputs.front() {
+            if seq.wrapping_sub(input.seq) as i32 <= 0 {
+                break
+            }
+            self.pending_inputs.pop_front();
+        }
+        let predicted = match self.pending_inputs.front() {
+            Some((input, predicted)) if input.seq == seq => *predicted,
+            // from before the last spawn
+            _ => return
+        };
+        self.pending_inputs.pop_front();
+        if predicted.0.distance(pos.0) <= consts::RECONCILE_DISTANCE {
+            return
+        }
+        self.player_pos.pos = pos;
+        self.player_phys = phys;
+        for (input, predicted) in self.pending_inputs.iter_mut() {
+            input.apply(&mut self.player_pos, &mut self.player_phys, &self.world.blocks);
+            *predicted = self.player_pos.pos;
+        }
+    }
+
+}
@@ -6,8 +6,9 @@ use crate::rustcraft::inventory::*;
 
 pub enum GameState {
     Inventory {
-        //aux_inventory: Box<dyn InventoryShell>,
         inventory: Box<dyn InventoryShell>,
+        /// Inventory of a used block, shown beside the player's
+        aux_inventory: Option<Box<dyn InventoryShell>>,
         picked_item: Option<ItemStack>,
     },
     Playing {
@@ -17,6 +18,8 @@ pub enum GameState {
     Chat {
         start_frame: Instant,
         text: Text,
+        /// Sign the line is written on, otherwise it is run as a command
+        sign: Option<BlockPos>,
     }
 }
 
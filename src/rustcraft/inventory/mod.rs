@@ -1,12 +1,16 @@
 
+#[cfg(feature = "client")]
 pub mod render;
 
 use crate::util::fdiv;
+#[cfg(feature = "client")]
 use crate::gui::render::Cursor;
 use crate::player::inventory::PlayerInventory;
+#[cfg(feature = "client")]
 use crate::gui::render::GUIRenderer;
 use crate::prelude::*;
 
+#[cfg(feature = "client")]
 pub trait InventoryShell {
     fn dyn_clone(&self) -> Box<dyn InventoryShell>;
     fn texture(&self) -> &Texture;
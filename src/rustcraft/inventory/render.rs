@@ -19,7 +19,7 @@ impl InventoryRenderer {
         }
         self.render_bottom(pgui, d, m);
         if show_inventory {
-            self.render_centered(&pgui.inventory, d, m);
+            self.render_centered(&pgui.inventory, d, m, 0);
             if let Some(item) = picked_item.as_ref().map(|s| &s.item) {
                 self.render_floating_item(item, m);
             }
@@ -29,7 +29,16 @@ impl InventoryRenderer {
         }
     }
 
-    pub fn corner_cursor(&mut self, i: &impl InventoryShell, m: PixelPos) -> Option<usize> {
+    /// Horizontal offsets from the centre, in GUI pixels, that put `left` and `right` side by side
+    pub fn side_by_side(left: &(impl InventoryShell + ?Sized), right: &(impl InventoryShell + ?Sized)) -> (i32, i32) {
+        const GAP: i32 = 4;
+        let (lw, rw) = (left.texture().size().0 as i32, right.texture().size().0 as i32);
+        let total = lw + GAP + rw;
+        (lw / 2 - total / 2, total / 2 - rw / 2)
+    }
+
+    /// Slot of `i` under the cursor, with `i` centred and shifted by `dx` GUI pixels
+    pub fn corner_cursor(&mut self, i: &(impl InventoryShell + ?Sized), m: PixelPos, dx: i32) -> Option<usize> {
         let (w,h) = (900, 700);
         let (hw, hh) = (450, 350);
 
@@ -39,7 +48,7 @@ impl InventoryRenderer {
 
         t.bind();
         self.gui.set_pixels(hw, hh);
-        self.gui.move_pixels(-iw / 2, -ih / 2);
+        self.gui.move_pixels(-iw / 2 + dx, -ih / 2);
         self.gui.set_uniforms(iw, ih);
         self.gui.square.draw();
 
@@ -96,7 +105,8 @@ impl InventoryRenderer {
 
     }
 
-    pub fn render_centered(&mut self, i: &impl InventoryShell, d: &impl InventoryData, m: PixelPos) {
+    /// Render `i` centred and shifted by `dx` GUI pixels
+    pub fn render_centered(&mut self, i: &(impl InventoryShell + ?Sized), d: &impl InventoryData, m: PixelPos, dx: i32) {
         self.gui.square.bind();
 
         let (w,h) = (900, 700);
@@ -108,7 +118,7 @@ impl InventoryRenderer {
 
         t.bind();
         self.gui.set_pixels(hw, hh);
-        self.gui.move_pixels(-iw / 2, -ih / 2);
+        self.gui.move_pixels(-iw / 2 + dx, -ih / 2);
         self.gui.set_uniforms(iw, ih);
         self.gui.square.draw();
 
@@ -123,7 +133,7 @@ impl InventoryRenderer {
 
     }
 
-    pub fn render_priv(&mut self, i: &impl InventoryShell, d: &impl InventoryData, mp: Option<(i32, i32)>, c: (i32, i32)) {
+    pub fn render_priv(&mut self, i: &(impl InventoryShell + ?Sized), d: &impl InventoryData, mp: Option<(i32, i32)>, c: (i32, i32)) {
 
         let (cx, cy) = self.gui.cursor.pos.into();
 
@@ -1,4 +1,6 @@
 
+use std::collections::BTreeMap;
+use crate::content::BehaviorRegistry;
 use crate::prelude::*;
 
 #[derive(Debug, Clone)]
@@ -73,15 +75,16 @@ impl std::fmt::Debug for Behavior {
 #[derive(Clone, Debug)]
 pub struct Block(pub Arc<(BlockData,bool)>);
 
+// blocks as items are copies made before the registry numbered them, so they are told apart by string id
 impl Eq for Block {}
 impl PartialEq for Block {
     fn eq(&self, rhs: &Self) -> bool {
-        Arc::ptr_eq(&self.0, &rhs.0)
+        self.0.0.id == rhs.0.0.id
     }
 }
 impl std::hash::Hash for Block {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        std::ptr::hash(self.0.as_ref(), state);
+        self.0.0.id.hash(state);
     }
 }
 
@@ -149,18 +152,223 @@ pub struct BlockData {
     #[serde(default)]
     pub no_render: bool,
     pub texture: (usize,usize,usize),
+    /// Texture of the face a `facing` property points to, instead of the one `texture` gives it
+    #[serde(default)]
+    pub front: Option<usize>,
     #[serde(default)]
     pub drops: Option<String>,
+    /// Time to dig the block, as a multiple of `consts::BREAK_TICKS`
+    #[serde(default = "one")]
+    pub hardness: f32,
+    /// Data created for every placed block, like the slots of a chest
+    #[serde(default)]
+    pub block_entity: Option<BlockEntityKind>,
     #[serde(skip)]
     pub behavior: Option<Box<Behavior>>,
+    /// Assigned by the `BlockRegistry`
+    #[serde(skip)]
+    pub num_id: BlockId,
+    /// Properties of the block, every combination of their values is a state of its own
+    #[serde(default)]
+    pub properties: BTreeMap<String, PropertyDef>,
+    /// Changes to the states matching them, applied in order
+    #[serde(default)]
+    pub states: Vec<StateOverride>,
+    /// Property values of this state, in the order of `properties`
+    #[serde(skip)]
+    pub state: Vec<(String, String)>,
+    /// Id of this state, like `log[axis=x]`, or just the block id if it has no properties
+    #[serde(skip)]
+    pub state_id: String,
+}
+
+/// Values a block property can take, the first one is the default
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum PropertyDef {
+    /// `true` or `false`, the given one being the default
+    Bool(bool),
+    /// Whole numbers from `min` to `max`
+    Range { min: i64, max: i64 },
+    /// One of the listed names
+    Enum(Vec<String>),
+}
+
+impl PropertyDef {
+    /// Possible values as written in state ids, the default first
+    pub fn values(&self) -> Vec<String> {
+        match self {
+            Self::Bool(default) => vec![default.to_string(), (!default).to_string()],
+            Self::Range { min, max } => (*min..=*max).map(|i| i.to_string()).collect(),
+            Self::Enum(names) => names.clone(),
+        }
+    }
+
+    /// Number of possible values without listing them, `None` if it does not fit a `usize`
+    pub fn value_count(&self) -> Option<usize> {
+        match self {
+            Self::Bool(_) => Some(2),
+            Self::Range { min, max } => std::convert::TryFrom::try_from((*max as i128 - *min as i128 + 1).max(0)).ok(),
+            Self::Enum(names) => Some(names.len()),
+        }
+    }
+}
+
+/// Changes for the states of a block whose property values match `when`
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct StateOverride {
+    #[serde(default)]
+    pub when: BTreeMap<String, toml::Value>,
+    pub texture: Option<(usize,usize,usize)>,
+    pub front: Option<usize>,
+    pub light: Option<u8>,
+    pub solid: Option<bool>,
+    pub transparent: Option<bool>,
+    /// Behavior names by event, like `on_update = "update/gravity"`
+    #[serde(default)]
+    pub behavior: BTreeMap<String, String>,
+}
+
+impl StateOverride {
+    fn matches(&self, state: &[(String, String)]) -> bool {
+        self.when.iter().all(|(name, value)| {
+            let value = match value {
+                toml::Value::String(s) => s.clone(),
+                value => value.to_string(),
+            };
+            state.iter().any(|(n, v)| n == name && *v == value)
+        })
+    }
 }
 
 impl BlockData {
     pub fn render_eq(&self, rhs: &Self) -> bool {
         self.texture == rhs.texture
+        && self.front == rhs.front
+        && self.property("facing") == rhs.property("facing")
         && self.transparent == rhs.transparent
         && self.no_render == rhs.no_render
     }
+
+    /// Ticks the block has to be dug before it breaks
+    pub fn break_ticks(&self) -> u64 {
+        (self.hardness * consts::BREAK_TICKS as f32).ceil() as u64
+    }
+
+    /// Value of the property `name` in this state
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.state.iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Id of the state that has `value` for the property `name` and the values of this one otherwise
+    pub fn with_property(&self, name: &str, value: &str) -> String {
+        state_id(&self.id, self.state.iter().map(|(n, v)| (n.as_str(), if n == name {value} else {v.as_str()})))
+    }
+
+    /// Atlas index of the texture on `face`. The `axis` property turns the top and bottom textures to its faces,
+    /// the `facing` property puts the `front` texture on the face it names.
+    pub fn face_texture(&self, face: Face) -> usize {
+        if let Some(front) = self.front {
+            let facing = match self.property("facing") {
+                Some("east") => Some(Face::XPos),
+                Some("west") => Some(Face::XNeg),
+                Some("up") => Some(Face::YPos),
+                Some("down") => Some(Face::YNeg),
+                Some("south") => Some(Face::ZPos),
+                Some("north") => Some(Face::ZNeg),
+                _ => None,
+            };
+            if facing == Some(face) {
+                return front
+            }
+        }
+        let (pos, neg) = match self.property("axis") {
+            Some("x") => (Face::XPos, Face::XNeg),
+            Some("z") => (Face::ZPos, Face::ZNeg),
+            _ => (Face::YPos, Face::YNeg),
+        };
+        if face == pos {
+            self.texture.0
+        } else if face == neg {
+            self.texture.2
+        } else {
+            self.texture.1
+        }
+    }
+
+    /// Number of states `expand_states` gives, `None` if it does not fit a `usize`
+    pub fn state_count(&self) -> Option<usize> {
+        self.properties.values()
+            .try_fold(1usize, |n, def| n.checked_mul(def.value_count()?.max(1)))
+    }
+
+    /// Every state of the block, the default one first, with the overrides applied
+    /// and behaviors named by them looked up in `behaviors`
+    pub fn expand_states(&self, behaviors: &BehaviorRegistry) -> Vec<BlockData> {
+        let props = self.properties.iter()
+            .map(|(name, def)| (name.clone(), def.values()))
+            .filter(|(_, values)| !values.is_empty())
+            .collect::<Vec<_>>();
+        let count = props.iter().map(|(_, values)| values.len()).product::<usize>();
+        (0..count)
+            .map(|mut n| {
+                // the first property changes slowest
+                let mut state = vec![];
+                for (name, values) in props.iter().rev() {
+                    state.push((name.clone(), values[n % values.len()].clone()));
+                    n /= values.len();
+                }
+                state.reverse();
+                let mut data = self.clone();
+                for o in self.states.iter().filter(|o| o.matches(&state)) {
+                    data.apply(o, behaviors);
+                }
+                data.state_id = state_id(&self.id, state.iter().map(|(n, v)| (n.as_str(), v.as_str())));
+                data.state = state;
+                data
+            })
+            .collect()
+    }
+
+    fn apply(&mut self, o: &StateOverride, behaviors: &BehaviorRegistry) {
+        if let Some(texture) = o.texture {self.texture = texture}
+        if let Some(front) = o.front {self.front = Some(front)}
+        if let Some(light) = o.light {self.light = light}
+        if let Some(solid) = o.solid {self.solid = solid}
+        if let Some(transparent) = o.transparent {self.transparent = transparent}
+        for (event, name) in &o.behavior {
+            let f = match behaviors.behaviors.get(name) {
+                Some(f) => *f,
+                None => {
+                    println!("Unknown behavior {} for block {}", name, self.id);
+                    continue
+                }
+            };
+            let behavior = self.behavior.get_or_insert_with(Default::default);
+            match event.as_str() {
+                "on_use" => behavior.on_use = Some(f),
+                "on_hit" => behavior.on_hit = Some(f),
+                "on_place" => behavior.on_place = Some(f),
+                "on_update" => behavior.on_update = Some(f),
+                "on_break" => behavior.on_break = Some(f),
+                "on_rnd_tick" => behavior.on_rnd_tick = Some(f),
+                _ => println!("Unknown block event {} for block {}", event, self.id),
+            }
+        }
+    }
+}
+
+/// State id of the block `id` with the given property values, like `log[axis=x]`
+pub fn state_id<'a>(id: &str, state: impl Iterator<Item = (&'a str, &'a str)>) -> String {
+    let props = state.map(|(n, v)| format!("{}={}", n, v)).collect::<Vec<_>>();
+    if props.is_empty() {
+        id.to_owned()
+    } else {
+        format!("{}[{}]", id, props.join(","))
+    }
 }
 
 const fn yes() -> bool {true}
+const fn one() -> f32 {1.}
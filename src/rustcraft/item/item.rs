@@ -1,7 +1,7 @@
 
 use crate::prelude::*;
 
-#[derive(Eq, PartialEq, Clone, Debug, Hash, serde::Deserialize)]
+#[derive(Eq, PartialEq, Clone, Debug, Hash, serde::Serialize, serde::Deserialize)]
 pub struct ItemData {
     pub id: ArcStr,
     pub name: String,
@@ -2,10 +2,12 @@ pub mod block;
 pub mod item;
 pub mod items;
 pub mod stack;
+#[cfg(feature = "client")]
 pub mod render_gui;
 
 pub use block::*;
 pub use item::*;
 pub use items::*;
 pub use stack::*;
+#[cfg(feature = "client")]
 pub use render_gui::*;
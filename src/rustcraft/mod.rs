@@ -1,5 +1,7 @@
 
+#[cfg(feature = "client")]
 pub mod game_loop;
+#[cfg(feature = "client")]
 pub mod input;
 pub mod world;
 // pub mod world2;
@@ -13,6 +15,8 @@ pub mod cmd;
 pub mod chunk;
 pub mod worker;
 pub mod settings;
+#[cfg(feature = "client")]
 pub mod data;
 pub mod entity;
-pub mod inventory;
\ No newline at end of file
+pub mod inventory;
+pub mod save;
\ No newline at end of file
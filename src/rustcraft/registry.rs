@@ -1,5 +1,6 @@
 
-use crate::TextureAtlas;
+use std::io;
+use crate::content::BehaviorRegistry;
 use crate::prelude::*;
 
 pub struct ItemRegistry {
@@ -12,3 +13,135 @@ impl ItemRegistry {
     }
 }
 
+/// Numeric id of a block state, only meaningful together with the `BlockRegistry` that assigned it
+pub type BlockId = u16;
+
+/// Block ids by string id, index `i` holding the block with numeric id `i`.
+/// Saved with a world so the ids it was played with stay the same when mods are added.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct BlockIdMap(pub Vec<String>);
+
+/// All block states with the numeric ids assigned at `ContentBuilder::finish`
+#[derive(Default)]
+pub struct BlockRegistry {
+    /// Indexed by numeric id, `None` for ids of a saved map whose block is not loaded
+    blocks: Vec<Option<BlockData>>,
+    names: BlockIdMap,
+    /// Numeric ids by state id
+    ids: HashMap<String, BlockId>,
+    /// Numeric ids of the default states by block id
+    defaults: HashMap<String, BlockId>,
+}
+
+impl BlockRegistry {
+
+    /// Expand the blocks into their states and number them, keeping the ids `known` already gives them.
+    /// New states follow in the order of their block ids, with air first in an empty map.
+    /// Fails naming the block whose states do not fit into a `BlockId` anymore.
+    pub fn new(blocks: impl IntoIterator<Item = BlockData>, known: &BlockIdMap, behaviors: &BehaviorRegistry) -> io::Result<Self> {
+        let too_many = |id: &str| io::Error::new(io::ErrorKind::InvalidData, format!(
+            "the states of block {} do not fit into the {} block ids", id, BlockId::MAX
+        ));
+        let mut blocks = blocks.into_iter().collect::<Vec<_>>();
+        blocks.sort_by_key(|b| (b.id != "air", b.id.clone()));
+        // counted before expanding, so a huge property range fails without being listed
+        if let Some(block) = blocks.iter().find(|b| b.state_count().map_or(true, |n| n > BlockId::MAX as usize)) {
+            return Err(too_many(&block.id))
+        }
+        let mut defaults = vec![];
+        let mut order = vec![];
+        let mut by_name = HashMap::new();
+        for block in &blocks {
+            let states = block.expand_states(behaviors);
+            defaults.push((block.id.clone(), states[0].state_id.clone()));
+            for state in states {
+                order.push(state.state_id.clone());
+                by_name.insert(state.state_id.clone(), state);
+            }
+        }
+        let new = order.into_iter()
+            .filter(|id| !known.0.contains(id))
+            .collect::<Vec<_>>();
+        if known.0.len() > BlockId::MAX as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                "{} saved block ids do not fit into {}", known.0.len(), BlockId::MAX
+            )))
+        }
+        if let Some(id) = new.get(BlockId::MAX as usize - known.0.len()) {
+            return Err(too_many(&by_name[id].id))
+        }
+        let mut reg = Self::default();
+        for id in known.0.iter().chain(&new) {
+            let num_id = reg.blocks.len() as BlockId;
+            let block = by_name.remove(id).map(|mut b| {
+                b.num_id = num_id;
+                b
+            });
+            if block.is_some() {
+                reg.ids.insert(id.clone(), num_id);
+            }
+            reg.blocks.push(block);
+            reg.names.0.push(id.clone());
+        }
+        reg.defaults = defaults.into_iter()
+            .map(|(id, state)| (id, reg.ids[&state]))
+            .collect();
+        Ok(reg)
+    }
+
+    /// Block state by state id, or the default state by block id
+    pub fn get(&self, id: &str) -> Option<&BlockData> {
+        self.by_id(self.id_of(id)?)
+    }
+
+    pub fn by_id(&self, id: BlockId) -> Option<&BlockData> {
+        self.blocks.get(id as usize)?.as_ref()
+    }
+
+    pub fn id_of(&self, id: &str) -> Option<BlockId> {
+        self.ids.get(id).or_else(|| self.defaults.get(id)).copied()
+    }
+
+    /// Loaded block states in the order of their ids
+    pub fn iter(&self) -> impl Iterator<Item = &BlockData> {
+        self.blocks.iter().flatten()
+    }
+
+    /// State id of the numeric id `id`, also for states that are not loaded
+    pub fn state_id(&self, id: BlockId) -> Option<&str> {
+        self.names.0.get(id as usize).map(String::as_str)
+    }
+
+    /// The mapping to save with a world, including ids of states that are not loaded
+    pub fn id_map(&self) -> &BlockIdMap {
+        &self.names
+    }
+
+    /// State of `block` to place against `face` of the clicked block, by a player looking along `heading`.
+    /// An `axis` property follows the face and a `facing` property turns towards the player.
+    pub fn placement_state<'a>(&'a self, block: &'a BlockData, face: Face, heading: Vector3<f32>) -> &'a BlockData {
+        let mut state = block;
+        if state.property("axis").is_some() {
+            let axis = match face {
+                Face::XPos | Face::XNeg => "x",
+                Face::YPos | Face::YNeg => "y",
+                Face::ZPos | Face::ZNeg => "z",
+            };
+            if let Some(s) = self.get(&state.with_property("axis", axis)) {
+                state = s;
+            }
+        }
+        if state.property("facing").is_some() {
+            let d = -heading;
+            let mut towards = [("east", d.x), ("west", -d.x), ("up", d.y), ("down", -d.y), ("south", d.z), ("north", -d.z)];
+            towards.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+            // the closest direction the block can face, blocks without up and down stay level
+            if let Some(s) = towards.iter().find_map(|(name, _)| self.get(&state.with_property("facing", name))) {
+                state = s;
+            }
+        }
+        state
+    }
+
+}
@@ -0,0 +1,114 @@
+use std::path::{Path, PathBuf};
+use std::io::{self, BufReader, BufWriter};
+use std::fs::File;
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use crate::prelude::*;
+
+/// Snapshot of all files in a world directory, with paths relative to it
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Backup {
+    pub files: Vec<(PathBuf, Vec<u8>)>,
+}
+
+/// Files of the world directory `dir` as paths relative to it and full paths, in a stable order
+fn world_files(dir: &Path) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut files = vec![];
+    let mut dirs = vec![dir.to_owned()];
+    while let Some(d) = dirs.pop() {
+        for entry in std::fs::read_dir(&d)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().map_or(false, |e| e == "tmp") {
+                // left over from an interrupted write
+                continue
+            } else {
+                let rel = path.strip_prefix(dir).unwrap().to_owned();
+                files.push((rel, path));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn invalid(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+impl Backup {
+
+    pub fn of_dir(dir: &Path) -> io::Result<Self> {
+        let files = world_files(dir)?.into_iter()
+            .map(|(rel, path)| Ok((rel, std::fs::read(path)?)))
+            .collect::<io::Result<_>>()?;
+        Ok(Self { files })
+    }
+
+    /// Read an archive written by `backup_world`
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let mut input = GzDecoder::new(BufReader::new(File::open(path)?));
+        let count: u64 = bincode::deserialize_from(&mut input).map_err(invalid)?;
+        let files = (0..count)
+            .map(|_| bincode::deserialize_from(&mut input).map_err(invalid))
+            .collect::<io::Result<_>>()?;
+        Ok(Self { files })
+    }
+
+    /// Write the backed up files into `dir`.
+    /// Backups may come from elsewhere, so nothing is written unless every path stays inside `dir`.
+    pub fn restore(&self, dir: &Path) -> io::Result<()> {
+        for (rel, _) in &self.files {
+            let inside = !rel.as_os_str().is_empty()
+                && rel.components().all(|c| matches!(c, std::path::Component::Normal(_)));
+            if !inside {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                    "backed up file {} is outside the world directory", rel.display()
+                )))
+            }
+        }
+        for (rel, bytes) in &self.files {
+            let path = dir.join(rel);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            super::write_atomic(&path, bytes)?;
+        }
+        Ok(())
+    }
+
+}
+
+/// Archive the world directory `dir` into the backups directory, returning the archive path.
+/// The archive is gzip compressed, with the file count followed by a `(relative path, contents)` pair per file,
+/// which are read and written one at a time.
+pub fn backup_world(dir: &Path) -> io::Result<PathBuf> {
+    let name = dir.file_name().unwrap_or_default().to_string_lossy();
+    let time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let out = Path::new(consts::BACKUPS_DIR);
+    std::fs::create_dir_all(out)?;
+    // backups within the same millisecond get a counter
+    let mut path = out.join(format!("{}-{}.bak.gz", name, time));
+    let mut n = 1;
+    while path.exists() {
+        path = out.join(format!("{}-{}-{}.bak.gz", name, time, n));
+        n += 1;
+    }
+    let mut tmp_name = path.file_name().unwrap_or_default().to_owned();
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+    {
+        let files = world_files(dir)?;
+        let mut output = GzEncoder::new(BufWriter::new(File::create(&tmp)?), Compression::default());
+        bincode::serialize_into(&mut output, &(files.len() as u64)).map_err(invalid)?;
+        for (rel, path) in files {
+            bincode::serialize_into(&mut output, &(rel, std::fs::read(path)?)).map_err(invalid)?;
+        }
+        output.finish()?.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    }
+    std::fs::rename(tmp, &path)?;
+    Ok(path)
+}
@@ -0,0 +1,289 @@
+use hecs::serialize::column::*;
+use hecs::{Archetype, ColumnBatchType, ColumnBatchBuilder};
+use serde::de::SeqAccess;
+use serde::ser::SerializeTuple;
+use std::io;
+use crate::prelude::*;
+use component::*;
+
+/// Serializable form of an `ItemStack`, referring to the item by id
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SavedStack {
+    pub id: String,
+    pub count: usize,
+}
+
+impl ItemStack {
+    pub fn to_saved(&self) -> SavedStack {
+        SavedStack {
+            id: self.item.id().to_owned(),
+            count: self.count,
+        }
+    }
+    pub fn from_saved(saved: &SavedStack, reg: &ItemRegistry) -> Option<Self> {
+        let item = reg.items.get(&saved.id).or_else(|| {
+            println!("Unknown item {} in saved stack", saved.id);
+            None
+        })?;
+        Some(ItemStack::of(item.clone(), saved.count))
+    }
+}
+
+/// Stand-in for `ItemCmp` while stored
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+struct SavedItem {
+    stack: SavedStack,
+    age: usize,
+}
+
+/// Stand-in for `PlayerData` while stored
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+struct SavedPlayer {
+    inventory: Vec<Option<SavedStack>>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+enum ComponentId {
+    Position,
+    Physics,
+    View,
+    Item,
+    WanderingAI,
+    PathFinding,
+    FallingBlock,
+    Player,
+    Model,
+}
+
+/// Column (de)serialization of the stored component set.
+/// Worlds passed to it should only contain the types listed in `ComponentId`.
+#[derive(Default)]
+struct Context {
+    components: Vec<ComponentId>,
+}
+
+macro_rules! for_each_component {
+    ($m:ident) => {
+        $m!(Position, Position);
+        $m!(Physics, Physics);
+        $m!(View, View);
+        $m!(Item, SavedItem);
+        $m!(WanderingAI, WanderingAI);
+        $m!(PathFinding, PathFinding);
+        $m!(FallingBlock, FallingBlock);
+        $m!(Player, SavedPlayer);
+        $m!(Model, ModelDesc);
+    };
+}
+
+impl SerializeContext for Context {
+    fn component_count(&self, archetype: &Archetype) -> usize {
+        archetype.component_types().len()
+    }
+
+    fn serialize_component_ids<S: SerializeTuple>(&mut self, archetype: &Archetype, out: &mut S) -> Result<(), S::Error> {
+        macro_rules! ser_id {
+            ($id:ident, $t:ty) => {
+                try_serialize_id::<$t, _, _>(archetype, &ComponentId::$id, out)?;
+            };
+        }
+        for_each_component!(ser_id);
+        Ok(())
+    }
+
+    fn serialize_components<S: SerializeTuple>(&mut self, archetype: &Archetype, out: &mut S) -> Result<(), S::Error> {
+        macro_rules! ser {
+            ($id:ident, $t:ty) => {
+                try_serialize::<$t, _>(archetype, out)?;
+            };
+        }
+        for_each_component!(ser);
+        Ok(())
+    }
+}
+
+impl DeserializeContext for Context {
+    fn deserialize_component_ids<'de, A: SeqAccess<'de>>(&mut self, mut seq: A) -> Result<ColumnBatchType, A::Error> {
+        self.components.clear();
+        let mut batch = ColumnBatchType::new();
+        while let Some(id) = seq.next_element()? {
+            macro_rules! add {
+                ($id:ident, $t:ty) => {
+                    if let ComponentId::$id = id {
+                        batch.add::<$t>();
+                    }
+                };
+            }
+            for_each_component!(add);
+            self.components.push(id);
+        }
+        Ok(batch)
+    }
+
+    fn deserialize_components<'de, A: SeqAccess<'de>>(&mut self, entity_count: u32, mut seq: A, batch: &mut ColumnBatchBuilder) -> Result<(), A::Error> {
+        for component in &self.components {
+            macro_rules! de {
+                ($id:ident, $t:ty) => {
+                    if let ComponentId::$id = component {
+                        deserialize_column::<$t, _>(entity_count, &mut seq, batch)?;
+                    }
+                };
+            }
+            for_each_component!(de);
+        }
+        Ok(())
+    }
+}
+
+/// Copy the storable components of `ents` into a new world
+fn stash(ecs: &hecs::World, ents: impl Iterator<Item = Entity>) -> hecs::World {
+    let mut out = hecs::World::new();
+    for ent in ents {
+        let mut b = hecs::EntityBuilder::new();
+        macro_rules! copy {
+            ($t:ty) => {
+                if let Ok(c) = ecs.get::<$t>(ent) {
+                    b.add((*c).clone());
+                }
+            };
+        }
+        copy!(Position);
+        copy!(Physics);
+        copy!(View);
+        copy!(WanderingAI);
+        copy!(PathFinding);
+        copy!(FallingBlock);
+        copy!(ModelDesc);
+        if let Ok(item) = ecs.get::<ItemCmp>(ent) {
+            b.add(SavedItem {
+                stack: item.item.to_saved(),
+                age: item.age,
+            });
+        }
+        if let Ok(pdata) = ecs.get::<PlayerData>(ent) {
+            b.add(SavedPlayer {
+                inventory: pdata.inventory.data.iter()
+                    .map(|s| s.as_ref().map(ItemStack::to_saved))
+                    .collect()
+            });
+        }
+        out.spawn(b.build());
+    }
+    out
+}
+
+/// Spawn the entities of a stashed world, returning the new entities
+fn unstash(stashed: &hecs::World, ents: &mut EntityData, reg: &ItemRegistry) -> Vec<Entity> {
+    let mut spawned = vec![];
+    for (_, e) in stashed.iter() {
+        let mut b = hecs::EntityBuilder::new();
+        macro_rules! copy {
+            ($t:ty) => {
+                if let Some(c) = e.get::<$t>() {
+                    b.add((*c).clone());
+                }
+            };
+        }
+        copy!(Position);
+        copy!(Physics);
+        copy!(View);
+        copy!(WanderingAI);
+        copy!(PathFinding);
+        copy!(FallingBlock);
+        copy!(ModelDesc);
+        if let Some(item) = e.get::<SavedItem>() {
+            match ItemStack::from_saved(&item.stack, reg) {
+                Some(stack) => {
+                    b.add(ItemCmp {
+                        item: stack,
+                        age: item.age,
+                    });
+                }
+                // drop the entity, the item no longer exists
+                None => continue
+            }
+        }
+        if let Some(player) = e.get::<SavedPlayer>() {
+            let mut pdata = PlayerData::new();
+            for (slot, stack) in pdata.inventory.data.iter_mut().zip(&player.inventory) {
+                *slot = stack.as_ref().and_then(|s| ItemStack::from_saved(s, reg));
+            }
+            b.add(pdata);
+        }
+        let ent = ents.ecs.spawn(b.build());
+        if let Ok(pos) = ents.ecs.get::<Position>(ent) {
+            ents.tree.insert(ent, ent, &pos.get_aabb());
+        }
+        spawned.push(ent);
+    }
+    spawned
+}
+
+fn to_bytes(stashed: &hecs::World) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    serialize(stashed, &mut Context::default(), &mut bincode::Serializer::new(&mut bytes, bincode::options()))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(bytes)
+}
+
+fn from_bytes(bytes: &[u8]) -> io::Result<hecs::World> {
+    deserialize(&mut Context::default(), &mut bincode::Deserializer::from_slice(bytes, bincode::options()))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Serialize all non-player entities positioned inside the chunk `pos`
+pub fn save_chunk_entities(ents: &EntityData, pos: ChunkPos) -> io::Result<Vec<u8>> {
+    let in_chunk = ents.ecs.query::<&Position>()
+        .without::<PlayerData>()
+        .iter()
+        .filter(|(_, p)| p.pos.as_chunk() == pos)
+        .map(|(ent, _)| ent)
+        .collect::<Vec<_>>();
+    if in_chunk.is_empty() {
+        return Ok(vec![])
+    }
+    to_bytes(&stash(&ents.ecs, in_chunk.into_iter()))
+}
+
+/// Spawn entities saved with `save_chunk_entities`
+pub fn load_entities(ents: &mut EntityData, bytes: &[u8], reg: &ItemRegistry) -> io::Result<Vec<Entity>> {
+    if bytes.is_empty() {
+        return Ok(vec![])
+    }
+    Ok(unstash(&from_bytes(bytes)?, ents, reg))
+}
+
+pub fn save_player(ents: &EntityData, player: Entity) -> io::Result<Vec<u8>> {
+    let bytes = to_bytes(&stash(&ents.ecs, std::iter::once(player)))?;
+    Ok(super::with_header(super::migration::PLAYER_MAGIC, bytes))
+}
+
+/// Spawn a player entity saved with `save_player`
+pub fn load_player(ents: &mut EntityData, bytes: &[u8], reg: &ItemRegistry) -> io::Result<Entity> {
+    let (version, bytes) = super::split_header(super::migration::PLAYER_MAGIC, bytes)?;
+    let bytes = super::migration::migrate_entities(version, bytes.to_owned())?;
+    let mut loaded = unstash(&from_bytes(&bytes)?, ents, reg).into_iter();
+    let player = loaded.next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no player in player data"))?;
+    for extra in loaded {
+        ents.despawn(extra);
+    }
+    Ok(player)
+}
+
+/// Change the ids of all item stacks in serialized entities
+pub fn rename_items(bytes: &[u8], rename: impl Fn(&str) -> String) -> io::Result<Vec<u8>> {
+    if bytes.is_empty() {
+        return Ok(vec![])
+    }
+    let mut stashed = from_bytes(bytes)?;
+    for (_, item) in stashed.query_mut::<&mut SavedItem>() {
+        item.stack.id = rename(&item.stack.id);
+    }
+    for (_, player) in stashed.query_mut::<&mut SavedPlayer>() {
+        for stack in player.inventory.iter_mut().flatten() {
+            stack.id = rename(&stack.id);
+        }
+    }
+    to_bytes(&stashed)
+}
@@ -0,0 +1,163 @@
+use std::path::{Path, PathBuf};
+use std::io;
+use crate::prelude::*;
+
+/// Name and version of a content mod a world was played with
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ModInfo {
+    pub name: String,
+    pub version: String,
+    /// Hash of the content registered by the mod, only used for networking
+    #[serde(skip)]
+    pub hash: u64,
+}
+
+/// Terrain generator name and its options
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct GeneratorSettings {
+    pub name: String,
+    #[serde(default)]
+    pub options: toml::value::Table,
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> Self {
+        Self {
+            name: "island".into(),
+            options: Default::default(),
+        }
+    }
+}
+
+/// Contents of the `level.toml` file in a world directory
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct LevelData {
+    /// Save format version, 0 for worlds from before it was recorded
+    #[serde(default)]
+    pub format_version: u32,
+    pub seed: String,
+    #[serde(default)]
+    pub ticks: u64,
+    /// Ticks into the day-night cycle
+    #[serde(default)]
+    pub day_time: u64,
+    /// Numeric block ids the world was played with, has to come before the tables for toml
+    #[serde(default)]
+    pub block_ids: BlockIdMap,
+    #[serde(default = "default_spawn")]
+    pub spawn: WorldPos,
+    #[serde(default)]
+    pub generator: GeneratorSettings,
+    #[serde(default)]
+    pub mods: Vec<ModInfo>,
+}
+
+fn default_spawn() -> WorldPos {
+    (50., 55., 50.).into()
+}
+
+impl LevelData {
+
+    pub fn new(seed: &str, content: &Content) -> Self {
+        Self {
+            format_version: super::FORMAT_VERSION,
+            seed: seed.to_owned(),
+            ticks: 0,
+            day_time: 0,
+            spawn: default_spawn(),
+            generator: GeneratorSettings::default(),
+            mods: content.mods.clone(),
+            block_ids: content.blocks.id_map().clone(),
+        }
+    }
+
+    /// Read the level file, upgrading it if it was saved with an older format version
+    pub fn read(dir: &Path) -> io::Result<Self> {
+        let tomlstr = std::fs::read_to_string(dir.join("level.toml"))?;
+        let mut table: toml::value::Table = toml::from_str(&tomlstr).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let version = table.get("format_version").and_then(|v| v.as_integer()).unwrap_or(0) as u32;
+        super::migration::check_version(version)?;
+        super::migration::migrate_level(version, &mut table);
+        let mut level: Self = toml::Value::Table(table).try_into().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if version < super::FORMAT_VERSION {
+            println!("Upgrading level of {} from format version {}", dir.display(), version);
+            level.format_version = super::FORMAT_VERSION;
+            level.write(dir)?;
+        }
+        Ok(level)
+    }
+
+    pub fn write(&self, dir: &Path) -> io::Result<()> {
+        super::write_atomic(&dir.join("level.toml"), self.to_toml()?.as_bytes())
+    }
+
+    /// Contents of the level file
+    pub fn to_toml(&self) -> io::Result<String> {
+        toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Fail unless `content` numbers blocks the way the world was saved, keeping every saved id.
+    /// Content built with `ContentBuilder::finish_with_ids` and these ids passes.
+    pub fn check_block_ids(&self, content: &Content) -> io::Result<()> {
+        let ids = &content.blocks.id_map().0;
+        match self.block_ids.0.iter().zip(ids).position(|(saved, id)| saved != id) {
+            Some(i) => Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                "block id {} is {} but the world was saved with {}", i, ids[i], self.block_ids.0[i]
+            ))),
+            None if ids.len() < self.block_ids.0.len() => Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                "{} block ids but the world was saved with {}", ids.len(), self.block_ids.0.len()
+            ))),
+            None => Ok(())
+        }
+    }
+
+    /// Warn about mods the world was saved with that are missing or have changed version
+    pub fn check_mods(&self, content: &Content) {
+        for m in &self.mods {
+            match content.mods.iter().find(|c| c.name == m.name) {
+                None => println!("World was saved with mod {} which is not loaded", m.name),
+                Some(c) if c.version != m.version => println!(
+                    "World was saved with mod {} version {}, loaded version is {}",
+                    m.name, m.version, c.version
+                ),
+                _ => {}
+            }
+        }
+    }
+
+}
+
+pub fn world_dir(name: &str) -> PathBuf {
+    Path::new(consts::SAVES_DIR).join(name)
+}
+
+pub fn world_exists(name: &str) -> bool {
+    world_dir(name).join("level.toml").exists()
+}
+
+/// Create the directory and level file of a new world
+pub fn create_world(name: &str, level: &LevelData) -> io::Result<()> {
+    if world_exists(name) {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("world {} already exists", name)))
+    }
+    let dir = world_dir(name);
+    std::fs::create_dir_all(&dir)?;
+    level.write(&dir)
+}
+
+/// Names of all worlds in the saves directory
+pub fn list_worlds() -> io::Result<Vec<String>> {
+    let dir = Path::new(consts::SAVES_DIR);
+    if !dir.exists() {
+        return Ok(vec![])
+    }
+    let mut names = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.path().join("level.toml").exists() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
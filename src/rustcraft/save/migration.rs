@@ -0,0 +1,125 @@
+use std::io;
+use crate::registry::BlockIdMap;
+
+/// Version of the save format written by this build.
+/// Bump it when adding a `Migration` from the previous version.
+pub const FORMAT_VERSION: u32 = 3;
+
+pub const REGION_MAGIC: &[u8; 4] = b"RCRG";
+pub const PLAYER_MAGIC: &[u8; 4] = b"RCPL";
+pub const RNG_MAGIC: &[u8; 4] = b"RCRN";
+
+pub enum Step {
+    /// A block or item id changed, applies to the world's block ids and saved item stacks
+    RenameId { from: &'static str, to: &'static str },
+    /// Rewrite the block ids of a world, e.g. to merge blocks by renaming one to the other.
+    /// Entries must keep their place, as regions refer to them by index.
+    RemapPalette(fn(&mut Vec<String>)),
+    /// Re-encode the contents of a region file saved with the previous layout, given the world's block ids
+    Region(fn(&[u8], &BlockIdMap) -> io::Result<Vec<u8>>),
+    /// Map the stored index of a `ChunkState`, e.g. after a variant was inserted
+    RemapChunkState(fn(u32) -> u32),
+    /// Edit the raw contents of `level.toml`
+    Level(fn(&mut toml::value::Table)),
+}
+
+/// Steps upgrading data saved with format version `from` to `from + 1`
+pub struct Migration {
+    pub from: u32,
+    pub steps: &'static [Step],
+}
+
+/// All migrations, ordered by version
+pub static MIGRATIONS: &[Migration] = &[
+    // files from before version headers, the data itself is unchanged
+    Migration { from: 0, steps: &[] },
+    // regions gained block entities
+    Migration { from: 1, steps: &[Step::Region(super::region::add_block_entities)] },
+    // region palettes became numeric ids of the world's block ids
+    Migration { from: 2, steps: &[Step::Region(super::region::numeric_palettes)] },
+];
+
+/// Steps needed to upgrade data saved with format version `version`, in order
+pub fn steps_since(version: u32) -> impl Iterator<Item = &'static Step> {
+    MIGRATIONS.iter()
+        .filter(move |m| m.from >= version)
+        .flat_map(|m| m.steps.iter())
+}
+
+/// Current id of something saved as `id` with format version `version`
+pub fn rename_id(version: u32, id: &str) -> String {
+    let mut id = id.to_owned();
+    for step in steps_since(version) {
+        if let Step::RenameId { from, to } = step {
+            if id == *from {
+                id = (*to).to_owned();
+            }
+        }
+    }
+    id
+}
+
+pub fn has_renames(version: u32) -> bool {
+    steps_since(version).any(|s| matches!(s, Step::RenameId {..}))
+}
+
+/// Upgrade serialized entities saved with format version `version`
+pub fn migrate_entities(version: u32, bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+    if has_renames(version) {
+        super::rename_items(&bytes, |id| rename_id(version, id))
+    } else {
+        Ok(bytes)
+    }
+}
+
+/// Upgrade the raw contents of a `level.toml` saved with format version `version`
+pub fn migrate_level(version: u32, level: &mut toml::value::Table) {
+    for step in steps_since(version) {
+        match step {
+            Step::Level(f) => f(level),
+            Step::RenameId { from, to } => {
+                let ids = level.get_mut("block_ids").and_then(|ids| ids.as_array_mut());
+                for id in ids.into_iter().flatten().filter(|id| id.as_str() == Some(*from)) {
+                    *id = toml::Value::String((*to).to_owned());
+                }
+            }
+            Step::RemapPalette(f) => {
+                if let Some(ids) = level.get("block_ids").and_then(|ids| ids.as_array()) {
+                    let mut ids = ids.iter().filter_map(|id| id.as_str().map(str::to_owned)).collect();
+                    f(&mut ids);
+                    level.insert("block_ids".into(), toml::Value::Array(ids.into_iter().map(toml::Value::String).collect()));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Prefix the contents of a binary save file with its kind and the current format version
+pub fn with_header(magic: &[u8; 4], mut bytes: Vec<u8>) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len() + 8);
+    out.extend_from_slice(magic);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.append(&mut bytes);
+    out
+}
+
+/// Format version and contents of a binary save file.
+/// Files written before headers were added have version 0.
+pub fn split_header<'a>(magic: &[u8; 4], bytes: &'a [u8]) -> io::Result<(u32, &'a [u8])> {
+    if bytes.len() < 8 || &bytes[..4] != magic {
+        return Ok((0, bytes))
+    }
+    let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    check_version(version)?;
+    Ok((version, &bytes[8..]))
+}
+
+pub fn check_version(version: u32) -> io::Result<()> {
+    if version > FORMAT_VERSION {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+            "saved with format version {}, newest supported is {}", version, FORMAT_VERSION
+        )))
+    }
+    Ok(())
+}
@@ -0,0 +1,28 @@
+pub mod region;
+pub mod level;
+pub mod entities;
+pub mod backup;
+pub mod migration;
+
+pub use region::*;
+pub use level::*;
+pub use entities::*;
+pub use backup::*;
+pub use migration::{FORMAT_VERSION, with_header, split_header};
+
+use std::path::Path;
+use std::io::{self, Write};
+
+/// Write `bytes` to a temporary file next to `path`, then rename it over `path`,
+/// so a crash leaves either the old or the new file behind, never a partial one
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(".tmp");
+    let tmp = path.with_file_name(name);
+    {
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+    }
+    std::fs::rename(tmp, path)
+}
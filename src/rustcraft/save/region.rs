@@ -0,0 +1,520 @@
+use std::path::{Path, PathBuf};
+use std::io;
+use crate::chunk::Light;
+use crate::chunk::palette::BlockStorage;
+use crate::prelude::*;
+use super::migration::{self, Step};
+use super::SavedStack;
+
+/// Position of a region, measured in regions
+pub type RegionPos = (i32, i32, i32);
+
+pub fn region_of(pos: ChunkPos) -> RegionPos {
+    let s = consts::REGION_SIZE;
+    (pos.x.div_euclid(s), pos.y.div_euclid(s), pos.z.div_euclid(s))
+}
+
+/// Owned chunk data, independent of any loaded content.
+/// Blocks are stored as indices into the chunk's own palette of block ids.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SavedChunk {
+    pub pos: ChunkPos,
+    pub state: ChunkState,
+    pub palette: Vec<String>,
+    /// Palette indices in x-y-z order
+    pub blocks: Vec<u16>,
+    /// Raw light values in x-y-z order
+    pub light: Vec<u8>,
+    /// Column serialized entities, see `save_chunk_entities`
+    pub entities: Vec<u8>,
+    /// Block entities ordered by position
+    pub block_entities: Vec<(BlockPos, SavedBlockEntity)>,
+}
+
+impl SavedChunk {
+
+    /// Chunk at `pos` filled with air and without light
+    pub fn new(pos: ChunkPos) -> Self {
+        SavedChunk {
+            pos,
+            state: ChunkState::Empty,
+            palette: vec!["air".to_owned()],
+            blocks: vec![0; 16*16*16],
+            light: vec![0; 16*16*16],
+            entities: vec![],
+            block_entities: vec![],
+        }
+    }
+
+    /// Set the block at chunk relative `x`, `y`, `z` to the one with id `id`
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, id: &str) {
+        let idx = match self.palette.iter().position(|b| b == id) {
+            Some(idx) => idx,
+            None => {
+                self.palette.push(id.to_owned());
+                self.palette.len() - 1
+            }
+        };
+        self.blocks[(x * 16 + y) * 16 + z] = idx as u16;
+    }
+
+}
+
+/// Stand-in for `BlockEntity` while stored
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum SavedBlockEntity {
+    Inventory(Vec<Option<SavedStack>>),
+    Furnace {
+        slots: Vec<Option<SavedStack>>,
+        burn_ticks: u32,
+        progress: u32,
+    },
+    Sign(String),
+}
+
+impl SavedBlockEntity {
+    /// Whether blocks with block entities of `kind` carry this one
+    pub fn is_kind(&self, kind: &BlockEntityKind) -> bool {
+        matches!(
+            (self, kind),
+            (Self::Inventory(_), BlockEntityKind::Inventory(_))
+                | (Self::Furnace {..}, BlockEntityKind::Furnace)
+                | (Self::Sign(_), BlockEntityKind::Sign)
+        )
+    }
+    fn stacks_mut(&mut self) -> impl Iterator<Item = &mut SavedStack> {
+        let slots = match self {
+            Self::Inventory(slots) | Self::Furnace { slots, .. } => &mut slots[..],
+            Self::Sign(_) => &mut [],
+        };
+        slots.iter_mut().flatten()
+    }
+}
+
+impl BlockEntity {
+    pub fn to_saved(&self) -> SavedBlockEntity {
+        let save = |slots: &Vec<Option<ItemStack>>| slots.iter()
+            .map(|s| s.as_ref().map(ItemStack::to_saved))
+            .collect();
+        match self {
+            Self::Inventory(slots) => SavedBlockEntity::Inventory(save(slots)),
+            Self::Furnace { slots, burn_ticks, progress } => SavedBlockEntity::Furnace {
+                slots: save(slots),
+                burn_ticks: *burn_ticks,
+                progress: *progress,
+            },
+            Self::Sign(text) => SavedBlockEntity::Sign(text.clone()),
+        }
+    }
+    pub fn from_saved(saved: &SavedBlockEntity, reg: &ItemRegistry) -> Self {
+        let load = |slots: &Vec<Option<SavedStack>>| slots.iter()
+            .map(|s| s.as_ref().and_then(|s| ItemStack::from_saved(s, reg)))
+            .collect();
+        match saved {
+            SavedBlockEntity::Inventory(slots) => Self::Inventory(load(slots)),
+            SavedBlockEntity::Furnace { slots, burn_ticks, progress } => Self::Furnace {
+                slots: load(slots),
+                burn_ticks: *burn_ticks,
+                progress: *progress,
+            },
+            SavedBlockEntity::Sign(text) => Self::Sign(text.clone()),
+        }
+    }
+}
+
+/// Chunk as stored inside a region
+#[derive(serde::Serialize, serde::Deserialize)]
+struct RegionChunk {
+    pos: ChunkPos,
+    /// Index into `ChunkState::ALL`
+    state: u32,
+    /// Numeric ids of the world's `BlockIdMap`
+    palette: Vec<BlockId>,
+    /// Indices into `palette` in x-y-z order
+    blocks: Vec<u16>,
+    light: Vec<u8>,
+    entities: Vec<u8>,
+    block_entities: Vec<(BlockPos, SavedBlockEntity)>,
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+pub struct Region {
+    chunks: HashMap<ChunkPos, RegionChunk>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+/// `RegionChunk` as saved before format version 2, without block entities
+#[derive(serde::Deserialize)]
+struct RegionChunkV1 {
+    pos: ChunkPos,
+    state: u32,
+    blocks: Vec<u16>,
+    light: Vec<u8>,
+    entities: Vec<u8>,
+}
+
+/// `Region` as saved before format version 2
+#[derive(serde::Deserialize)]
+struct RegionV1 {
+    palette: Vec<String>,
+    chunks: HashMap<ChunkPos, RegionChunkV1>,
+}
+
+/// `RegionChunk` as saved before format version 3, with indices into the region palette
+#[derive(serde::Serialize, serde::Deserialize)]
+struct RegionChunkV2 {
+    pos: ChunkPos,
+    state: u32,
+    blocks: Vec<u16>,
+    light: Vec<u8>,
+    entities: Vec<u8>,
+    block_entities: Vec<(BlockPos, SavedBlockEntity)>,
+}
+
+/// `Region` as saved before format version 3, with a palette of block state ids
+#[derive(serde::Serialize, serde::Deserialize)]
+struct RegionV2 {
+    palette: Vec<String>,
+    chunks: HashMap<ChunkPos, RegionChunkV2>,
+}
+
+fn invalid(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Format version 2 migration, adding the block entities to the chunks
+pub fn add_block_entities(bytes: &[u8], _: &BlockIdMap) -> io::Result<Vec<u8>> {
+    let old: RegionV1 = bincode::deserialize(bytes).map_err(invalid)?;
+    let chunks = old.chunks.into_iter()
+        .map(|(pos, c)| (pos, RegionChunkV2 {
+            pos: c.pos,
+            state: c.state,
+            blocks: c.blocks,
+            light: c.light,
+            entities: c.entities,
+            block_entities: vec![],
+        }))
+        .collect();
+    bincode::serialize(&RegionV2 { palette: old.palette, chunks }).map_err(invalid)
+}
+
+/// Format version 3 migration, replacing the region palette of block state ids with a palette
+/// of numeric ids per chunk. States the world has no id for become air.
+pub fn numeric_palettes(bytes: &[u8], block_ids: &BlockIdMap) -> io::Result<Vec<u8>> {
+    let old: RegionV2 = bincode::deserialize(bytes).map_err(invalid)?;
+    let air = block_ids.0.iter().position(|id| id == "air").unwrap_or(0) as BlockId;
+    let palette = old.palette.iter()
+        .map(|id| {
+            let id = migration::rename_id(2, id);
+            match block_ids.0.iter().position(|known| *known == id) {
+                Some(num_id) => num_id as BlockId,
+                None => {
+                    println!("Block {} has no id in the world, replacing it with air", id);
+                    air
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut chunks = HashMap::new();
+    for (pos, c) in old.chunks {
+        let mut remap = HashMap::new();
+        let mut chunk_palette = vec![];
+        let mut blocks = Vec::with_capacity(c.blocks.len());
+        for i in &c.blocks {
+            let num_id = *palette.get(*i as usize).ok_or_else(|| invalid(format!(
+                "block index {} is outside the region palette of {}", i, palette.len()
+            )))?;
+            let idx = *remap.entry(num_id).or_insert_with(|| {
+                chunk_palette.push(num_id);
+                chunk_palette.len() as u16 - 1
+            });
+            blocks.push(idx);
+        }
+        chunks.insert(pos, RegionChunk {
+            pos: c.pos,
+            state: c.state,
+            palette: chunk_palette,
+            blocks,
+            light: c.light,
+            entities: c.entities,
+            block_entities: c.block_entities,
+        });
+    }
+    bincode::serialize(&Region { chunks, dirty: false }).map_err(invalid)
+}
+
+impl Region {
+
+    /// Read a region file, upgrading it if it was saved with an older format version.
+    /// `block_ids` are the ids of the world the region belongs to.
+    pub fn read(path: &Path, block_ids: &BlockIdMap) -> io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let (version, bytes) = migration::split_header(migration::REGION_MAGIC, &bytes)?;
+        let mut bytes = bytes.to_owned();
+        for step in migration::steps_since(version) {
+            if let Step::Region(f) = step {
+                bytes = f(&bytes, block_ids)?;
+            }
+        }
+        let mut region: Self = bincode::deserialize(&bytes).map_err(invalid)?;
+        if version < migration::FORMAT_VERSION {
+            println!("Upgrading region {} from format version {}", path.display(), version);
+            region.migrate(version)?;
+        }
+        Ok(region)
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let bytes = bincode::serialize(self).map_err(invalid)?;
+        super::write_atomic(path, &migration::with_header(migration::REGION_MAGIC, bytes))
+    }
+
+    /// Apply the migration steps to the contents of the chunks, block ids are upgraded with the level
+    fn migrate(&mut self, version: u32) -> io::Result<()> {
+        for step in migration::steps_since(version) {
+            if let Step::RemapChunkState(f) = step {
+                for c in self.chunks.values_mut() {
+                    c.state = f(c.state);
+                }
+            }
+        }
+        for c in self.chunks.values_mut() {
+            c.entities = migration::migrate_entities(version, std::mem::take(&mut c.entities))?;
+            if migration::has_renames(version) {
+                for stack in c.block_entities.iter_mut().flat_map(|(_, b)| b.stacks_mut()) {
+                    stack.id = migration::rename_id(version, &stack.id);
+                }
+            }
+        }
+        // written back in the current format on the next flush
+        self.dirty = true;
+        Ok(())
+    }
+
+    pub fn is_dirty(&self) -> bool {self.dirty}
+
+    /// The chunk at `pos`, `None` if it was never saved.
+    /// Fails if its blocks refer to ids `block_ids` or its palette do not have.
+    pub fn get(&self, pos: ChunkPos, block_ids: &BlockIdMap) -> io::Result<Option<SavedChunk>> {
+        let rc = match self.chunks.get(&pos) {
+            Some(rc) => rc,
+            None => return Ok(None)
+        };
+        let palette = rc.palette.iter()
+            .map(|id| block_ids.0.get(*id as usize).cloned().ok_or_else(|| invalid(format!(
+                "block id {} is outside the world's {} ids", id, block_ids.0.len()
+            ))))
+            .collect::<io::Result<Vec<_>>>()?;
+        if let Some(i) = rc.blocks.iter().find(|i| **i as usize >= palette.len()) {
+            return Err(invalid(format!("block index {} is outside the palette of {}", i, palette.len())))
+        }
+        let state = ChunkState::ALL.get(rc.state as usize).copied().unwrap_or_else(|| {
+            println!("Unknown state {} of saved chunk {:?}", rc.state, pos);
+            ChunkState::Empty
+        });
+        Ok(Some(SavedChunk {
+            pos,
+            state,
+            palette,
+            blocks: rc.blocks.clone(),
+            light: rc.light.clone(),
+            entities: rc.entities.clone(),
+            block_entities: rc.block_entities.clone(),
+        }))
+    }
+
+    /// Store `chunk`, numbering its blocks with `ids`, the numeric ids of the world's block state ids
+    pub fn insert(&mut self, chunk: SavedChunk, ids: &HashMap<String, BlockId>) -> io::Result<()> {
+        let palette = chunk.palette.iter()
+            .map(|id| ids.get(id).copied().ok_or_else(|| invalid(format!("block {} has no id in the world", id))))
+            .collect::<io::Result<Vec<_>>>()?;
+        self.chunks.insert(chunk.pos, RegionChunk {
+            pos: chunk.pos,
+            state: chunk.state as u32,
+            palette,
+            blocks: chunk.blocks,
+            light: chunk.light,
+            entities: chunk.entities,
+            block_entities: chunk.block_entities,
+        });
+        self.dirty = true;
+        Ok(())
+    }
+
+}
+
+/// Region files of a single world directory.
+/// At most `MAX_OPEN_REGIONS` are kept in memory, the least recently used are written and dropped first.
+pub struct RegionStore {
+    dir: PathBuf,
+    /// Block ids of the world, which region palettes refer to
+    block_ids: BlockIdMap,
+    /// Numeric ids by block state id, the first one for ids that appear more than once
+    ids: HashMap<String, BlockId>,
+    regions: HashMap<RegionPos, Region>,
+    /// Value of `clock` when each open region was last used
+    last_used: HashMap<RegionPos, u64>,
+    clock: u64,
+}
+
+impl RegionStore {
+
+    pub fn open(dir: impl Into<PathBuf>, block_ids: &BlockIdMap) -> io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(dir.join("region"))?;
+        let mut ids = HashMap::new();
+        for (i, id) in block_ids.0.iter().enumerate() {
+            ids.entry(id.clone()).or_insert(i as BlockId);
+        }
+        Ok(Self {
+            dir,
+            block_ids: block_ids.clone(),
+            ids,
+            regions: HashMap::new(),
+            last_used: HashMap::new(),
+            clock: 0,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {&self.dir}
+
+    fn region_path(&self, (x,y,z): RegionPos) -> PathBuf {
+        self.dir.join("region").join(format!("r.{}.{}.{}.rgn", x, y, z))
+    }
+
+    fn region(&mut self, rp: RegionPos) -> io::Result<&mut Region> {
+        if !self.regions.contains_key(&rp) {
+            self.evict(consts::MAX_OPEN_REGIONS - 1)?;
+            let path = self.region_path(rp);
+            let region = if path.exists() {
+                Region::read(&path, &self.block_ids)?
+            } else {
+                Region::default()
+            };
+            self.regions.insert(rp, region);
+        }
+        self.clock += 1;
+        self.last_used.insert(rp, self.clock);
+        Ok(self.regions.get_mut(&rp).unwrap())
+    }
+
+    /// Write and drop the least recently used regions until at most `keep` are open
+    fn evict(&mut self, keep: usize) -> io::Result<()> {
+        while self.regions.len() > keep {
+            let rp = match self.last_used.iter().min_by_key(|(_, used)| **used) {
+                Some((rp, _)) => *rp,
+                None => return Ok(())
+            };
+            let path = self.region_path(rp);
+            let region = self.regions.get_mut(&rp).unwrap();
+            if region.is_dirty() {
+                region.write(&path)?;
+                region.dirty = false;
+            }
+            self.regions.remove(&rp);
+            self.last_used.remove(&rp);
+        }
+        Ok(())
+    }
+
+    pub fn load_chunk(&mut self, pos: ChunkPos) -> io::Result<Option<SavedChunk>> {
+        let rp = region_of(pos);
+        self.region(rp)?;
+        self.regions[&rp].get(pos, &self.block_ids)
+    }
+
+    pub fn save_chunk(&mut self, chunk: SavedChunk) -> io::Result<()> {
+        let rp = region_of(chunk.pos);
+        self.region(rp)?;
+        self.regions.get_mut(&rp).unwrap().insert(chunk, &self.ids)
+    }
+
+    /// Write all modified regions to disk
+    pub fn flush(&mut self) -> io::Result<()> {
+        let dirty = self.regions.iter()
+            .filter(|(_, r)| r.is_dirty())
+            .map(|(rp, _)| *rp)
+            .collect::<Vec<_>>();
+        for rp in dirty {
+            let path = self.region_path(rp);
+            let region = self.regions.get_mut(&rp).unwrap();
+            region.write(&path)?;
+            region.dirty = false;
+        }
+        Ok(())
+    }
+
+}
+
+impl Chunk {
+
+    /// Saved form of the chunk, with its palette given by the state ids of `blocks`
+    pub fn to_saved(&self, blocks: &BlockRegistry) -> SavedChunk {
+        // states no block uses anymore are not saved
+        let mut data = self.data.clone();
+        data.compact();
+        let mut block_entities = self.block_entities.iter()
+            .map(|(pos, b)| (*pos, b.to_saved()))
+            .collect::<Vec<_>>();
+        block_entities.sort_by_key(|(pos, _)| pos.as_tuple());
+        let mut light = Vec::with_capacity(16*16*16);
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    light.push(self.light[x][y][z].raw());
+                }
+            }
+        }
+        SavedChunk {
+            pos: self.pos,
+            // meshes are not saved, so rendered chunks need to be meshed again
+            state: self.chunk_state.min(ChunkState::Detailed),
+            palette: data.palette().iter().map(|id| blocks.state_id(*id).unwrap_or("air").to_owned()).collect(),
+            blocks: data.indices().collect(),
+            light,
+            entities: vec![],
+            block_entities,
+        }
+    }
+
+    /// Chunk from its saved form, failing if the block or light data is incomplete
+    /// or refers to blocks outside its palette
+    pub fn from_saved(saved: &SavedChunk, reg: &Content) -> io::Result<Self> {
+        let volume = 16*16*16;
+        if saved.light.len() != volume {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} light values instead of {}", saved.light.len(), volume)))
+        }
+        let air = reg.blocks.id_of("air").unwrap();
+        let palette = saved.palette.iter()
+            .map(|id| reg.blocks.id_of(id).unwrap_or_else(|| {
+                println!("Unknown block {} in saved chunk {:?}", id, saved.pos);
+                air
+            }))
+            .collect::<Vec<_>>();
+        let mut chunk = Chunk::new(saved.pos, air);
+        chunk.data = BlockStorage::from_palette(palette, &saved.blocks)?;
+        let mut i = 0;
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    chunk.light[x][y][z] = Light::from_raw(saved.light[i]);
+                    i += 1;
+                }
+            }
+        }
+        for (pos, b) in &saved.block_entities {
+            // only blocks declaring a block entity of that kind carry one
+            let kind = reg.blocks.by_id(chunk.block_at(pos)).and_then(|block| block.block_entity.as_ref());
+            if pos.as_chunk() != saved.pos || !kind.map_or(false, |kind| b.is_kind(kind)) {
+                println!("Dropped block entity at {:?} in saved chunk {:?}, the block there has none of its kind", pos, saved.pos);
+                continue
+            }
+            chunk.block_entities.insert(*pos, BlockEntity::from_saved(b, &reg.items));
+        }
+        chunk.chunk_state = saved.state;
+        chunk.needs_refresh = true;
+        Ok(chunk)
+    }
+
+}
@@ -17,6 +17,9 @@ pub struct Settings {
     pub gui_scale: i32, // TODO implement
     #[serde(default)]
     pub debug: bool,
+    /// Player name used when joining worlds and servers
+    #[serde(default = "name_def")]
+    pub name: String,
 }
 
 impl Settings {
@@ -28,4 +31,5 @@ impl Settings {
 
 const fn one() -> f32 {1.}
 const fn two() -> i32 {2}
-const fn fov_def() -> Deg<f32> {Deg(90.)}
\ No newline at end of file
+const fn fov_def() -> Deg<f32> {Deg(90.)}
+fn name_def() -> String {"player".into()}
\ No newline at end of file
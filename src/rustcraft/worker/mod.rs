@@ -1,63 +1,238 @@
-/* 
-use std::sync::{Arc, mpsc::*};
+use std::sync::{Arc, Mutex, mpsc::*};
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+use crate::save::{RegionStore, SavedChunk};
 use crate::prelude::*;
 
-pub struct WorkerData {
-    pub registry: Arc<ItemRegistry>,
+pub enum WorkerJob {
+    SaveChunk(SavedChunk),
+    LoadChunk(ChunkPos),
+    /// Atomically write a file of the world directory, given by its path relative to it
+    WriteFile(PathBuf, Vec<u8>),
+    /// Read the saved data of the player with this name
+    LoadPlayer(String),
+    /// Write all modified regions to disk
+    Flush,
+    /// Flush, then archive the world directory
+    Backup,
+}
+
+pub enum WorkerResponse {
+    /// `None` if the chunk has never been saved
+    LoadedChunk(ChunkPos, Option<SavedChunk>),
+    /// `None` if the player has never joined
+    LoadedPlayer(String, std::io::Result<Option<Vec<u8>>>),
+    /// A `WriteFile` job finished, with the path it was given
+    Wrote(PathBuf),
+    BackedUp(PathBuf),
+    Failed(String),
 }
 
+/// Thread doing chunk I/O against a single world directory.
+/// Jobs run one at a time in the order they were queued, so a chunk read after it was saved gets the saved data.
+/// The job queue is bounded, so producers can back off using `try_send`.
 pub struct JobDispatcher {
-    tx: Sender<WorkerJob>,
+    tx: Option<SyncSender<WorkerJob>>,
     rx: Receiver<WorkerResponse>,
+    thread: Option<JoinHandle<RegionStore>>,
+    dir: PathBuf,
 }
 
 impl JobDispatcher {
 
+    pub fn new(storage: RegionStore) -> Self {
+        let dir = storage.dir().to_owned();
+        let (tx, rx) = sync_channel(consts::IO_QUEUE_SIZE);
+        let (dtx, drx) = channel();
+        let thread = std::thread::spawn(move || worker_thread(rx, dtx, storage));
+        JobDispatcher {
+            tx: Some(tx),
+            rx: drx,
+            thread: Some(thread),
+            dir,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {&self.dir}
+
     pub fn iter_responses(&mut self) -> TryIter<'_, WorkerResponse> {
         self.rx.try_iter()
     }
 
-    pub fn new(wdata: WorkerData) -> Self {
-        let (tx, rx) = std::sync::mpsc::channel();
-        let (dtx, drx) = std::sync::mpsc::channel();
-        std::thread::spawn(move || {
-            worker_thread(rx, dtx, wdata)
-        });
-        JobDispatcher {
-            tx,
-            rx: drx
+    /// Queue a job, handing it back if the queue is full
+    pub fn try_send(&self, work: WorkerJob) -> Result<(), WorkerJob> {
+        match self.tx.as_ref() {
+            Some(tx) => tx.try_send(work).map_err(|e| match e {
+                TrySendError::Full(work) | TrySendError::Disconnected(work) => work
+            }),
+            None => Err(work)
         }
     }
+
+    /// Queue a job, waiting for room in the queue if it is full.
+    /// Only for when the caller can afford to wait, like on shutdown.
     pub fn send(&self, work: WorkerJob) {
-        self.tx.send(work);
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(work);
+        }
+    }
+
+    /// Finish all queued jobs, stop the thread and write everything to disk
+    pub fn shutdown(&mut self) -> std::io::Result<()> {
+        self.tx = None;
+        match self.thread.take().map(JoinHandle::join) {
+            Some(Ok(mut storage)) => storage.flush(),
+            Some(Err(_)) => Err(std::io::Error::new(std::io::ErrorKind::Other, "chunk I/O thread panicked")),
+            None => Ok(())
+        }
     }
-}
 
-pub enum WorkerJob {
-    SaveChunk(Box<Chunk>),
-    LoadChunk(i32,i32,i32),
 }
-pub enum WorkerResponse {
-    LoadedChunk(Option<Box<Chunk>>),
+
+impl Drop for JobDispatcher {
+    fn drop(&mut self) {
+        if let Err(e) = self.shutdown() {
+            println!("Failed to flush chunk storage: {}", e);
+        }
+    }
 }
-fn worker_thread(rx: Receiver<WorkerJob>, tx: Sender<WorkerResponse>, data: WorkerData) {
-    'work: loop {
-        let job = match rx.recv() {
-            Err(_) => {break 'work}
-            Ok(job) => job
-        };
+
+fn worker_thread(rx: Receiver<WorkerJob>, tx: Sender<WorkerResponse>, mut storage: RegionStore) -> RegionStore {
+    for job in rx {
         use WorkerJob::*;
-        /* match job {
+        let res = match job {
             SaveChunk(chunk) => {
-                std::fs::write(format!("save/{:x}_{:x}_{:x}.chunk", chunk.pos.x, chunk.pos.y, chunk.pos.z), chunk.save());
+                let pos = chunk.pos;
+                storage.save_chunk(chunk)
+                    .map_err(|e| format!("Failed to save chunk {:?}: {}", pos, e))
+            },
+            LoadChunk(pos) => {
+                match storage.load_chunk(pos) {
+                    Ok(saved) => {
+                        let _ = tx.send(WorkerResponse::LoadedChunk(pos, saved));
+                        Ok(())
+                    },
+                    Err(e) => {
+                        // generate it instead of waiting forever
+                        let _ = tx.send(WorkerResponse::LoadedChunk(pos, None));
+                        Err(format!("Failed to load chunk {:?}: {}", pos, e))
+                    }
+                }
+            },
+            WriteFile(rel, bytes) => {
+                let path = storage.dir().join(&rel);
+                path.parent().map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|_| crate::save::write_atomic(&path, &bytes))
+                    .map(|_| {
+                        let _ = tx.send(WorkerResponse::Wrote(rel));
+                    })
+                    .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
             },
-            LoadChunk(x,y,z) => {
-                tx.send(
-                    WorkerResponse::LoadedChunk(
-                        Chunk::load(x, y, z, data.registry.as_ref()).map(Box::new)
-                    )
-                ).unwrap();
+            LoadPlayer(name) => {
+                let saved = read_player(storage.dir(), &name);
+                let _ = tx.send(WorkerResponse::LoadedPlayer(name, saved));
+                Ok(())
+            },
+            Flush => {
+                storage.flush()
+                    .map_err(|e| format!("Failed to write regions: {}", e))
+            },
+            Backup => {
+                storage.flush()
+                    .and_then(|_| crate::save::backup_world(storage.dir()))
+                    .map(|path| {
+                        let _ = tx.send(WorkerResponse::BackedUp(path));
+                    })
+                    .map_err(|e| format!("Failed to back up world: {}", e))
             }
-        }; */
+        };
+        if let Err(e) = res {
+            let _ = tx.send(WorkerResponse::Failed(e));
+        }
+    }
+    storage
+}
+
+/// Saved data of the player `name` in the world directory `dir`, if they joined before
+fn read_player(dir: &Path, name: &str) -> std::io::Result<Option<Vec<u8>>> {
+    let players = dir.join("players");
+    let path = players.join(format!("{}.dat", name));
+    // worlds from before multiplayer have a single player, given to whoever joins first
+    let legacy = dir.join("player.dat");
+    if !path.exists() && legacy.exists() {
+        std::fs::create_dir_all(&players)?;
+        std::fs::rename(legacy, &path)?;
+    }
+    if path.exists() {
+        std::fs::read(path).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Pool of threads running the same job function, with results collected by the owning thread
+pub struct WorkerPool<J, R> {
+    tx: Option<Sender<J>>,
+    rx: Receiver<R>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl<J: Send + 'static, R: Send + 'static> WorkerPool<J, R> {
+
+    pub fn new(threads: usize, work: impl Fn(J) -> R + Send + Sync + 'static) -> Self {
+        let (tx, rx) = channel::<J>();
+        let (dtx, drx) = channel();
+        let rx = Arc::new(Mutex::new(rx));
+        let work = Arc::new(work);
+        let threads = (0..threads.max(1))
+            .map(|_| {
+                let (rx, dtx, work) = (rx.clone(), dtx.clone(), work.clone());
+                std::thread::spawn(move || loop {
+                    let job = match rx.lock().unwrap().recv() {
+                        Ok(job) => job,
+                        Err(_) => return
+                    };
+                    if dtx.send(work(job)).is_err() {
+                        return
+                    }
+                })
+            })
+            .collect();
+        WorkerPool {
+            tx: Some(tx),
+            rx: drx,
+            threads,
+        }
+    }
+
+    pub fn send(&self, job: J) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(job);
+        }
+    }
+
+    /// Results of the jobs finished since the last call, in the order they finished
+    pub fn iter_done(&mut self) -> TryIter<'_, R> {
+        self.rx.try_iter()
+    }
+
+}
+
+/// Workers generating the terrain of chunks
+pub type TerrainWorkers = WorkerPool<ChunkPos, SavedChunk>;
+
+impl TerrainWorkers {
+    pub fn terrain(generator: Arc<dyn TerrainGenerator>) -> Self {
+        Self::new(consts::TERRAIN_WORKER_THREADS, move |pos| generator.gen_terrain(pos))
     }
-} */
\ No newline at end of file
+}
+
+impl<J, R> Drop for WorkerPool<J, R> {
+    fn drop(&mut self) {
+        // the workers stop once the queue is closed
+        self.tx = None;
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
@@ -10,7 +10,8 @@ pub fn gen_detail<'cnt>(pos: ChunkPos, world: &mut WorldData<'cnt>, reg: &'cnt C
         for z in z..z+16 {
             'yloop: for y in y..y+16 {
                 let below: BlockPos = (x,y-1,z).into();
-                if world.blocks.block_at_any_state(&below).unwrap().id == "grass" {
+                // the chunk below may have been unloaded
+                if world.blocks.block_at_any_state(&below).map_or(false, |b| b.id == "grass") {
                     let h = util::hash(&(x,z));
                     if h % 10 == 0 {
                         let h = 4 + h.rem_euclid(4) as i32; // 4..=7
@@ -1,11 +1,22 @@
 
+use crate::save::{GeneratorSettings, SavedChunk};
 use crate::prelude::*;
 
-pub trait TerrainGenerator {
+/// Construct the terrain generator with the given name, if it exists
+pub fn make_generator(settings: &GeneratorSettings, seed: &str) -> Option<Arc<dyn TerrainGenerator>> {
+    match settings.name.as_str() {
+        "island" => Some(IslandGenerator::from_options(seed, &settings.options)),
+        _ => None
+    }
+}
+
+/// Generates terrain independently of any loaded content, so it can run on worker threads
+pub trait TerrainGenerator: Send + Sync {
     //fn density(&self, x: isize, y: isize, z: isize) -> f64;
     //fn is_cave(&self, x: isize, y: isize, z: isize) -> bool;
     //fn palette(&self, x: isize, z: isize) -> &[&'static str; 3];
-    fn gen_terrain<'cnt>(&self, chunk: &mut Chunk<'cnt>, reg: &'cnt Content);
+    /// Blocks of the chunk at `pos`, in the `Filled` state
+    fn gen_terrain(&self, pos: ChunkPos) -> SavedChunk;
     fn get_detailer(&self) -> Box<dyn ChunkDetailer>;
 }
 
@@ -21,14 +32,14 @@ pub struct IslandGenerator {
 }
 
 impl TerrainGenerator for IslandGenerator {
-    fn gen_terrain<'cnt>(&self, chunk: &mut Chunk<'cnt>, reg: &'cnt Content) {
+    fn gen_terrain(&self, pos: ChunkPos) -> SavedChunk {
+        let mut chunk = SavedChunk::new(pos);
+        let pos = pos.map(|x| x as isize);
 
         for x in 0..16 {
             for z in 0..16 {
                 let palette = self.palette(x,z);
                 for y in 0..16 {
-                    let pos = chunk.pos.map(|x| x as isize);
-
                     let ax = 16 * pos.x + x;
                     let ay = 16 * pos.y + y;
                     let az = 16 * pos.z + z;
@@ -38,24 +49,22 @@ impl TerrainGenerator for IslandGenerator {
                     let d = self.density(ax,ay,az);
                     let da = self.density(ax,ay+1,az);
                     
-                    chunk.data[x as usize][y as usize][z as usize] = 
-                    &reg.blocks.get(
-                        if d > 0.56 {
-                            palette[0]
-                        } else if d > 0.52 {
-                            if da > 0.52 {
-                                palette[1]
-                            } else if ay >= 20 {
-                                palette[2]
-                            } else {
-                                palette[1]
-                            }
-                        } else if ay > 20 {
-                            "air"
+                    let id = if d > 0.56 {
+                        palette[0]
+                    } else if d > 0.52 {
+                        if da > 0.52 {
+                            palette[1]
+                        } else if ay >= 20 {
+                            palette[2]
                         } else {
-                            "water"
+                            palette[1]
                         }
-                    ).unwrap();
+                    } else if ay > 20 {
+                        "air"
+                    } else {
+                        "water"
+                    };
+                    chunk.set_block(x as usize, y as usize, z as usize, id);
                     /* if db > 0.52 && db < 0.56 && d < 0.51 && !(cb > 0.57) {
                         let t = noise.get2d([x as f64 / 1.5, z as f64 / 1.5]);
                         if t > 0.52 {
@@ -65,24 +74,33 @@ impl TerrainGenerator for IslandGenerator {
                 }
             }
         }
+        chunk.state = ChunkState::Filled;
+        chunk
     }
     fn get_detailer(&self) -> Box<dyn ChunkDetailer> {box IslandDetailer}
 }
 
 impl IslandGenerator {
 
-    pub fn new_dyn(seed: &str) -> Box<dyn TerrainGenerator> {
-        let noise = crate::perlin::PerlinNoise::new(seed, 4, 0.5);
+    pub fn new_dyn(seed: &str) -> Arc<dyn TerrainGenerator> {
+        Self::from_options(seed, &Default::default())
+    }
+
+    /// Supported options are `octaves` and `fallout` of the terrain noise
+    pub fn from_options(seed: &str, options: &toml::value::Table) -> Arc<dyn TerrainGenerator> {
+        let octaves = options.get("octaves").and_then(toml::Value::as_integer).unwrap_or(4) as usize;
+        let fallout = options.get("fallout").and_then(toml::Value::as_float).unwrap_or(0.5);
+        let noise = crate::perlin::PerlinNoise::new(seed, octaves, fallout);
         let noise_basic = crate::perlin::PerlinNoise::new(seed, 1, 1.);
         let palettes = [
             ["stone","dirt","grass"],
             ["stone","sand","sand"]
         ];
-        box IslandGenerator {
+        Arc::new(IslandGenerator {
             noise,
             noise_basic,
             palettes
-        } as Box<dyn TerrainGenerator>
+        })
     }
 
     fn is_cave(&self, x: isize, y: isize, z: isize) -> bool {
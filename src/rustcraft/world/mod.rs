@@ -3,81 +3,652 @@ mod data;
 mod raycast;
 mod generation;
 mod voxel_data;
+mod tickets;
 pub mod updates;
 
 use crate::world::updates::Updates;
+use crate::save::{RegionStore, LevelData, SavedChunk};
+use crate::worker::{JobDispatcher, TerrainWorkers, WorkerJob, WorkerResponse};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 pub use generation::*;
 pub use data::*;
 pub use raycast::*;
+pub use tickets::*;
+pub use gen::gen_detail;
 
 use crate::prelude::*;
 
-#[derive(Debug)]
-pub enum Loading {
-    Filling(i32, ChunkPos),
-    Detailing(i32, ChunkPos),
-    Meshing(i32, ChunkPos),
-}
-
 pub struct EntityData {
     pub ecs: hecs::World,
     pub tree: BVH<hecs::Entity, hecs::Entity>,
-    pub player: hecs::Entity,
+    /// Player controlled by this client, `None` on the server
+    pub player: Option<hecs::Entity>,
+}
+
+impl EntityData {
+
+    /// Spawn a new player entity at `pos`
+    pub fn spawn_player(&mut self, pos: WorldPos) -> hecs::Entity {
+        let (cmps, aabb) = make_player(pos);
+        let ent = self.ecs.spawn(cmps);
+        self.tree.insert(ent, ent, &aabb);
+        ent
+    }
+
+    pub fn despawn(&mut self, ent: hecs::Entity) {
+        self.tree.remove(ent);
+        let _ = self.ecs.despawn(ent);
+    }
+
+    /// Inventory of the player controlled by this client
+    pub fn local_player_data(&mut self) -> Option<&mut PlayerData> {
+        self.ecs.query_one_mut::<&mut PlayerData>(self.player?).ok()
+    }
+
 }
 
-pub struct ChunkData<'cnt> {
-    pub chunk: Box<Chunk<'cnt>>,
+pub struct ChunkData {
+    pub chunk: Box<Chunk>,
     /// Self is not treated as a neighbour, so count should be in 0..=26
     pub loaded_neighbours: usize,
 }
 
-impl<'cnt> ChunkData<'cnt> {
+impl ChunkData {
     pub fn all_neighbours_loaded(&self) -> bool {
         self.loaded_neighbours == 26
     }
 }
 
 pub struct VoxelData<'cnt> {
-    pub chunks: HashMap<ChunkPos, ChunkData<'cnt>>,
+    /// Resolves the numeric block ids chunks store
+    pub registry: &'cnt BlockRegistry,
+    pub chunks: HashMap<ChunkPos, ChunkData>,
     pub changed_chunks: HashSet<ChunkPos>,
+    /// Chunks modified since they were last saved
+    pub unsaved_chunks: HashSet<ChunkPos>,
+    /// Blocks set in interactable chunks since they were last sent to clients
+    pub changed_blocks: HashSet<BlockPos>,
+    /// Block entities of removed blocks, whose items have yet to be spilled
+    pub dropped_block_entities: Vec<(BlockPos, BlockEntity)>,
 }
 
 pub struct WorldData<'cnt> {
     pub force_loaded: HashSet<ChunkPos>,
+    /// Load levels from players, force loaded chunks and the spawn
+    pub tickets: LoadTickets,
     pub block_updates: Updates,
     pub entities: EntityData,
     pub blocks: VoxelData<'cnt>,
-    pub seed: String,
+    pub level: LevelData,
     pub air: &'cnt BlockData,
-    pub noise: Box<dyn TerrainGenerator>,
+    pub noise: Arc<dyn TerrainGenerator>,
     pub ticks: u64,
-    pub to_load: VecDeque<Loading>,
+    pub day_time: u64,
+    /// Generates the chunks load levels ask for
+    pub generator: chunk::gen::ChunkGenerator,
+    pub io: Option<JobDispatcher>,
+    /// Generates the terrain of chunks that were never saved, started when first needed
+    pub terrain: Option<TerrainWorkers>,
+    /// Chunks requested from `io` or `terrain` that have not arrived yet
+    pub requested_chunks: HashSet<ChunkPos>,
+    /// Chunks read by `io` or generated by `terrain`, waiting to be loaded
+    pub received_chunks: HashMap<ChunkPos, SavedChunk>,
+    /// Received chunks that were generated rather than read
+    pub generated_chunks: HashSet<ChunkPos>,
+    /// Chunks that contained entities when last saved
+    pub entity_chunks: HashSet<ChunkPos>,
+    /// A save is in progress, its chunks are handed to `io` as the queue has room
+    pub autosaving: bool,
+    /// Archive the world directory once the save in progress is written
+    pub backup_pending: bool,
+    /// Files of the world directory waiting for room in the I/O queue, by path relative to it
+    pub pending_writes: Vec<(std::path::PathBuf, Vec<u8>)>,
+    /// Saved data of the players who left, for when they join again before it is written.
+    /// Entries are removed once `io` has written them.
+    pub left_players: HashMap<String, Vec<u8>>,
+    /// Joining players whose saved data was asked from `io`
+    pub requested_players: HashSet<String>,
+    /// Saved data `io` read for joining players, `None` for new ones
+    pub loaded_players: HashMap<String, std::io::Result<Option<Vec<u8>>>>,
+    /// Player entities of everyone in the world, by name
+    pub players: HashMap<String, hecs::Entity>,
+    /// Source of all randomness in the simulation, saved with the world so replays can repeat it
+    pub rng: ChaCha8Rng,
+    /// Chunks handed from storage to loading since this was last cleared, in order
+    pub taken_chunks: Vec<ChunkPos>,
+    /// When set, only these chunks may be taken from storage.
+    /// Replays use it to load chunks at the same ticks as when they were recorded.
+    pub allowed_chunks: Option<HashSet<ChunkPos>>,
+    /// Blocks whose inventory the `on_use` behavior that just ran wants opened for the player using it
+    pub opened_inventories: Vec<BlockPos>,
+}
+
+/// Initial state of the world's random number generator, from the level seed
+fn seeded_rng(seed: &str) -> ChaCha8Rng {
+    let mut seed_buf = [0; 32];
+    for (i, b) in seed.bytes().cycle().take(32).enumerate() {
+        seed_buf[i] = b;
+    }
+    ChaCha8Rng::from_seed(seed_buf)
 }
 
 impl<'cnt> WorldData<'cnt> {
     
-    pub fn new(seed: &str, air: &'cnt BlockData) -> Self {
-        let noise = IslandGenerator::new_dyn(seed);
-        let mut ecs = hecs::World::new();
-        let mut tree = BVH::new();
-        let player = {
-            let (cam, aabb) = make_player();
-            let cam = ecs.spawn(cam);
-            tree.insert(cam, cam, &aabb);
-            cam
-        };
+    pub fn new(level: LevelData, registry: &'cnt BlockRegistry) -> Self {
+        let air = registry.get("air").unwrap();
+        let noise = make_generator(&level.generator, &level.seed)
+            .unwrap_or_else(|| {
+                println!("Unknown generator {}, using default", level.generator.name);
+                IslandGenerator::new_dyn(&level.seed)
+            });
         let entities = EntityData {
-            ecs,
-            tree,
-            player
+            ecs: hecs::World::new(),
+            tree: BVH::new(),
+            player: None,
         };
         let blocks = VoxelData {
+            registry,
             chunks: HashMap::new(),
             changed_chunks: HashSet::new(),
+            unsaved_chunks: HashSet::new(),
+            changed_blocks: HashSet::new(),
+            dropped_block_entities: vec![],
         };
         let block_updates = Updates::default();
         let force_loaded = HashSet::default();
-        WorldData { force_loaded, block_updates, entities, to_load: VecDeque::new(), seed: seed.to_owned(), blocks, noise, air, ticks: 0 }
+        let ticks = level.ticks;
+        let day_time = level.day_time;
+        let rng = seeded_rng(&level.seed);
+        WorldData { force_loaded, tickets: LoadTickets::default(), block_updates, entities, generator: Default::default(), level, blocks, noise, air, ticks, day_time, io: None, terrain: None, requested_chunks: HashSet::new(), received_chunks: HashMap::new(), generated_chunks: HashSet::new(), entity_chunks: HashSet::new(), autosaving: false, backup_pending: false, pending_writes: vec![], left_players: HashMap::new(), requested_players: HashSet::new(), loaded_players: HashMap::new(), players: HashMap::new(), rng, taken_chunks: vec![], allowed_chunks: None, opened_inventories: vec![] }
+    }
+
+    /// Create a new named world in the saves directory
+    pub fn create(name: &str, seed: &str, content: &'cnt Content) -> std::io::Result<Self> {
+        let level = LevelData::new(seed, content);
+        save::create_world(name, &level)?;
+        let mut world = Self::new(level, &content.blocks);
+        world.open_storage(save::world_dir(name))?;
+        Ok(world)
+    }
+
+    /// Open an existing named world from the saves directory
+    pub fn open(name: &str, content: &'cnt Content) -> std::io::Result<Self> {
+        let dir = save::world_dir(name);
+        let level = LevelData::read(&dir)?;
+        level.check_mods(content);
+        level.check_block_ids(content)?;
+        if make_generator(&level.generator, &level.seed).is_none() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("unknown generator {}", level.generator.name)))
+        }
+        let mut world = Self::new(level, &content.blocks);
+        world.level.mods = content.mods.clone();
+        if content.blocks.id_map().0.len() > world.level.block_ids.0.len() {
+            // regions may refer to the new ids as soon as they are written
+            world.level.block_ids = content.blocks.id_map().clone();
+            world.level.write(&dir)?;
+        }
+        let rng = dir.join("rng.dat");
+        if rng.exists() {
+            let bytes = std::fs::read(rng)?;
+            let (_, bytes) = save::split_header(save::migration::RNG_MAGIC, &bytes)?;
+            world.rng = bincode::deserialize(bytes)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        }
+        world.open_storage(dir)?;
+        Ok(world)
+    }
+
+    pub fn open_or_create(name: &str, seed: &str, content: &'cnt Content) -> std::io::Result<Self> {
+        if save::world_exists(name) {
+            Self::open(name, content)
+        } else {
+            Self::create(name, seed, content)
+        }
+    }
+
+    /// Use the world directory `dir` for loading and saving chunks
+    pub fn open_storage(&mut self, dir: impl Into<std::path::PathBuf>) -> std::io::Result<()> {
+        self.io = Some(JobDispatcher::new(RegionStore::open(dir, &self.level.block_ids)?));
+        Ok(())
+    }
+
+    /// Finish the save in progress and all pending chunk I/O, then stop using storage
+    pub fn close(&mut self) -> std::io::Result<()> {
+        if self.autosaving {
+            self.continue_save(usize::MAX, true);
+        }
+        self.queue_writes(true);
+        self.requested_chunks.clear();
+        self.received_chunks.clear();
+        self.generated_chunks.clear();
+        self.terrain = None;
+        match self.io.take() {
+            Some(mut io) => io.shutdown(),
+            None => Ok(())
+        }
+    }
+
+    /// Collect the results of finished I/O and generation jobs
+    pub fn poll_io(&mut self) {
+        let mut missing = vec![];
+        if let Some(io) = &mut self.io {
+            for res in io.iter_responses() {
+                match res {
+                    WorkerResponse::LoadedChunk(pos, Some(saved)) => {
+                        self.requested_chunks.remove(&pos);
+                        self.received_chunks.insert(pos, saved);
+                    }
+                    // stays requested until it is generated
+                    WorkerResponse::LoadedChunk(pos, None) => missing.push(pos),
+                    WorkerResponse::LoadedPlayer(name, saved) => {
+                        if self.requested_players.remove(&name) {
+                            self.loaded_players.insert(name, saved);
+                        }
+                    }
+                    WorkerResponse::Wrote(path) => {
+                        // a player who left can be read from their file now
+                        if path.parent() == Some(std::path::Path::new("players")) {
+                            if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                                self.left_players.remove(name);
+                            }
+                        }
+                    }
+                    WorkerResponse::BackedUp(path) => println!("Backup written to {}", path.display()),
+                    WorkerResponse::Failed(e) => println!("{}", e),
+                }
+            }
+        }
+        for pos in missing {
+            self.generate_chunk(pos);
+        }
+        if let Some(terrain) = &mut self.terrain {
+            for saved in terrain.iter_done() {
+                self.requested_chunks.remove(&saved.pos);
+                self.generated_chunks.insert(saved.pos);
+                self.received_chunks.insert(saved.pos, saved);
+            }
+        }
+    }
+
+    /// Queue the terrain of the chunk at `pos` for generation on the worker threads
+    fn generate_chunk(&mut self, pos: ChunkPos) {
+        let noise = &self.noise;
+        self.terrain
+            .get_or_insert_with(|| TerrainWorkers::terrain(noise.clone()))
+            .send(pos);
+    }
+
+    /// Generate the terrain of the chunk at `pos` again, in place of saved data that could not be loaded
+    pub fn regenerate_chunk(&mut self, pos: ChunkPos) {
+        self.requested_chunks.insert(pos);
+        self.generate_chunk(pos);
+    }
+
+    /// Ask `io` to read the chunk at `pos`, or the terrain workers to generate it if there is no storage,
+    /// unless it is already loaded or on its way
+    pub fn request_chunk(&mut self, pos: ChunkPos) {
+        if self.blocks.chunks.contains_key(&pos)
+            || self.requested_chunks.contains(&pos)
+            || self.received_chunks.contains_key(&pos) {
+            return
+        }
+        match &self.io {
+            Some(io) => {
+                // the queue is full, ask again later
+                if io.try_send(WorkerJob::LoadChunk(pos)).is_ok() {
+                    self.requested_chunks.insert(pos);
+                }
+            }
+            None => {
+                self.requested_chunks.insert(pos);
+                self.generate_chunk(pos);
+            }
+        }
+    }
+
+    /// Data of the chunk at `pos`, read from storage or generated,
+    /// `None` if it is still on its way
+    pub fn take_chunk(&mut self, pos: ChunkPos) -> Option<SavedChunk> {
+        if !self.allowed_chunks.as_ref().map_or(true, |a| a.contains(&pos)) {
+            self.request_chunk(pos);
+            return None
+        }
+        let saved = self.received_chunks.remove(&pos);
+        if saved.is_none() {
+            self.request_chunk(pos);
+        } else {
+            self.taken_chunks.push(pos);
+            if let Some(allowed) = &mut self.allowed_chunks {
+                allowed.remove(&pos);
+            }
+            if self.generated_chunks.remove(&pos) {
+                // never saved yet
+                self.blocks.unsaved_chunks.insert(pos);
+            }
+        }
+        saved
     }
 
-}
\ No newline at end of file
+    fn player_level(view_distance: i32) -> u8 {
+        ChunkLoadLevel::ENTITY_TICKING + view_distance.clamp(0, 64) as u8
+    }
+
+    /// Number of chunks a player keeps loaded at `view_distance`, if no other load source is near
+    pub fn chunks_per_player(view_distance: i32) -> usize {
+        // levels drop by one per chunk in every direction, chunks of level 1 and up are loaded
+        let side = 2 * (Self::player_level(view_distance) as usize - 1) + 1;
+        side.pow(3)
+    }
+
+    /// Place load sources at the players, force loaded chunks and the spawn, updating chunk load levels.
+    /// Chunks up to `view_distance` from a player simulate entities.
+    pub fn update_tickets(&mut self, view_distance: i32) {
+        let player_level = Self::player_level(view_distance);
+        let mut sources = vec![(self.level.spawn.as_chunk(), ChunkLoadLevel::ENTITY_TICKING + consts::SPAWN_CHUNK_RADIUS)];
+        for ent in self.players.values() {
+            if let Ok(pos) = self.entities.ecs.get::<Position>(*ent) {
+                sources.push((pos.pos.as_chunk(), player_level));
+            }
+        }
+        sources.extend(self.force_loaded.iter().map(|p| (*p, ChunkLoadLevel::ENTITY_TICKING)));
+        if self.tickets.set_sources(sources) {
+            self.generator.invalidate();
+        }
+    }
+
+    /// Show the inventory of the block entity at `pos` to the player using the block, or let them write on it if it is a sign,
+    /// called from `on_use` behaviors
+    pub fn open_inventory(&mut self, pos: BlockPos) {
+        self.opened_inventories.push(pos);
+    }
+
+    pub fn advance_tick(&mut self) {
+        self.ticks += 1;
+        self.day_time = (self.day_time + 1) % consts::DAY_NIGHT_DURATION_TICKS;
+    }
+
+    /// Queue the level metadata and players to be written to storage, if any
+    pub fn save_level(&mut self) -> std::io::Result<()> {
+        self.level.ticks = self.ticks;
+        self.level.day_time = self.day_time;
+        if self.io.is_some() {
+            self.pending_writes.push(("level.toml".into(), self.level.to_toml()?.into_bytes()));
+            let rng = bincode::serialize(&self.rng).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            self.pending_writes.push(("rng.dat".into(), save::with_header(save::migration::RNG_MAGIC, rng)));
+            let names = self.players.keys().cloned().collect::<Vec<_>>();
+            for name in names {
+                self.save_player(&name)?;
+            }
+            self.queue_writes(false);
+        }
+        Ok(())
+    }
+
+    /// Queue the player `name` to be written to storage, returning the saved data
+    fn save_player(&mut self, name: &str) -> std::io::Result<Option<Vec<u8>>> {
+        if let (Some(_), Some(ent)) = (&self.io, self.players.get(name)) {
+            let bytes = save::save_player(&self.entities, *ent)?;
+            self.pending_writes.push((std::path::Path::new("players").join(format!("{}.dat", name)), bytes.clone()));
+            return Ok(Some(bytes))
+        }
+        Ok(None)
+    }
+
+    /// Hand the pending file writes to `io` in order, stopping at a full queue unless `wait` is set.
+    /// Returns whether none are left.
+    fn queue_writes(&mut self, wait: bool) -> bool {
+        let io = match &self.io {
+            Some(io) => io,
+            None => return true
+        };
+        let mut queued = 0;
+        for (path, bytes) in self.pending_writes.iter() {
+            let job = WorkerJob::WriteFile(path.clone(), bytes.clone());
+            if wait {
+                io.send(job);
+            } else if io.try_send(job).is_err() {
+                break
+            }
+            queued += 1;
+        }
+        self.pending_writes.drain(..queued);
+        self.pending_writes.is_empty()
+    }
+
+    /// Have `io` read the saved data of the player `name` unless it is at hand, returns whether `join` can add them now.
+    /// The read is queued behind the pending file writes, so it sees the data of a player who just left.
+    pub fn request_player(&mut self, name: &str) -> bool {
+        if self.io.is_none() || self.players.contains_key(name) || self.left_players.contains_key(name) || self.loaded_players.contains_key(name) {
+            return true
+        }
+        if !self.requested_players.contains(name) && self.queue_writes(false) {
+            let queued = self.io.as_ref()
+                .map_or(false, |io| io.try_send(WorkerJob::LoadPlayer(name.to_owned())).is_ok());
+            if queued {
+                self.requested_players.insert(name.to_owned());
+            }
+        }
+        false
+    }
+
+    /// Spawn the player `name` where they left, or at the world spawn if they are new.
+    /// Worlds with storage have to read their data with `request_player` first.
+    pub fn join(&mut self, name: &str, items: &ItemRegistry) -> std::io::Result<hecs::Entity> {
+        if let Some(ent) = self.players.get(name) {
+            return Ok(*ent)
+        }
+        let saved = if let Some(bytes) = self.left_players.remove(name) {
+            // its file may not be written yet
+            Some(bytes)
+        } else if self.io.is_some() {
+            match self.loaded_players.remove(name) {
+                Some(saved) => saved?,
+                None => return Err(std::io::Error::new(std::io::ErrorKind::Other, "player data was not read yet")),
+            }
+        } else {
+            None
+        };
+        let ent = match saved {
+            Some(bytes) => save::load_player(&mut self.entities, &bytes, items)?,
+            None => self.entities.spawn_player(self.level.spawn),
+        };
+        self.players.insert(name.to_owned(), ent);
+        Ok(ent)
+    }
+
+    /// Save the player `name` and remove them from the world
+    pub fn leave(&mut self, name: &str) -> std::io::Result<()> {
+        let res = self.save_player(name).map(|saved| {
+            if let Some(bytes) = saved {
+                self.left_players.insert(name.to_owned(), bytes);
+            }
+            self.queue_writes(false);
+        });
+        if let Some(ent) = self.players.remove(name) {
+            self.entities.despawn(ent);
+        }
+        res
+    }
+
+    /// Mark the chunks whose entities may have changed since the last save as unsaved
+    fn mark_entity_chunks(&mut self) {
+        let current = self.entities.ecs.query::<&Position>()
+            .without::<PlayerData>()
+            .iter()
+            .map(|(_, p)| p.pos.as_chunk())
+            .collect::<HashSet<_>>();
+        // chunks entities have left need their entity list cleared
+        let previous = std::mem::replace(&mut self.entity_chunks, current);
+        self.blocks.unsaved_chunks.extend(previous);
+        self.blocks.unsaved_chunks.extend(self.entity_chunks.iter().copied());
+    }
+
+    /// Saved form of the chunk at `pos` with its entities, if it has been generated
+    fn saved_chunk(&self, pos: ChunkPos) -> std::io::Result<Option<SavedChunk>> {
+        match self.blocks.chunks.get(&pos) {
+            Some(c) if c.chunk.chunk_state >= ChunkState::Filled => {
+                let mut saved = c.chunk.to_saved(self.blocks.registry);
+                saved.entities = save::save_chunk_entities(&self.entities, pos)?;
+                Ok(Some(saved))
+            }
+            _ => Ok(None)
+        }
+    }
+
+    /// Write the level metadata and players to storage, then start saving all unsaved chunks with their entities.
+    /// The chunks are handed to `io` as its queue has room, the rest follow on the next ticks.
+    pub fn save_all(&mut self) -> std::io::Result<()> {
+        if self.io.is_none() {
+            return Ok(())
+        }
+        let res = self.save_level();
+        self.mark_entity_chunks();
+        self.autosaving = true;
+        self.continue_save(usize::MAX, false);
+        res
+    }
+
+    /// Called every game tick. Starts an autosave every `AUTOSAVE_INTERVAL_TICKS`
+    /// and hands the chunks of a save in progress to the I/O worker a few at a time.
+    pub fn autosave(&mut self) {
+        if self.io.is_none() {
+            return
+        }
+        if self.ticks % consts::AUTOSAVE_INTERVAL_TICKS == 0 {
+            if let Err(e) = self.save_level() {
+                println!("Autosave failed: {}", e);
+            }
+            self.mark_entity_chunks();
+            self.autosaving = true;
+        }
+        if self.autosaving {
+            self.continue_save(consts::AUTOSAVE_CHUNKS_PER_TICK, false);
+        } else {
+            self.queue_writes(false);
+        }
+    }
+
+    /// Hand up to `max` unsaved chunks to `io`, then flush or back up once none are left.
+    /// Stops early if the queue is full, unless `wait` is set.
+    fn continue_save(&mut self, max: usize, wait: bool) {
+        // the level is written first, so a backup includes it
+        if !self.queue_writes(wait) {
+            return
+        }
+        let io = match &self.io {
+            Some(io) => io,
+            None => return
+        };
+        let queue = |job| if wait {
+            io.send(job);
+            Ok(())
+        } else {
+            io.try_send(job)
+        };
+        let batch = self.blocks.unsaved_chunks.iter()
+            .copied()
+            .take(max)
+            .collect::<Vec<_>>();
+        for pos in batch {
+            match self.saved_chunk(pos) {
+                Ok(Some(saved)) => {
+                    // the worker is busy, continue next tick
+                    if queue(WorkerJob::SaveChunk(saved)).is_err() {
+                        return
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => println!("Failed to save chunk {:?}: {}", pos, e),
+            }
+            self.blocks.unsaved_chunks.remove(&pos);
+        }
+        if !self.blocks.unsaved_chunks.is_empty() {
+            return
+        }
+        // a backup flushes before archiving
+        let job = if self.backup_pending {WorkerJob::Backup} else {WorkerJob::Flush};
+        if queue(job).is_ok() {
+            self.autosaving = false;
+            self.backup_pending = false;
+        }
+    }
+
+    /// Remove the chunks no load source needs, then terrain-only chunks while more than `max_chunks` are loaded.
+    /// Ticking chunks are never removed, the limit can only be kept if load sources allow it.
+    pub fn unload_chunks(&mut self, max_chunks: usize) {
+        let mut candidates = self.blocks.chunks.keys()
+            .map(|p| (self.tickets.level_at(*p), p.as_tuple()))
+            .filter(|(level, _)| *level < ChunkLoadLevel::TICKING)
+            .collect::<Vec<_>>();
+        // the order entities are despawned in has to be the same in replays
+        candidates.sort();
+        let excess = self.blocks.chunks.len().saturating_sub(max_chunks);
+        let unload = candidates.into_iter()
+            .enumerate()
+            .take_while(|(i, (level, _))| *level == 0 || *i < excess)
+            .take(consts::UNLOAD_CHUNKS_PER_TICK)
+            .map(|(_, (_, p))| p.into())
+            .collect::<Vec<ChunkPos>>();
+        // chunks that arrived after they stopped being needed
+        let tickets = &self.tickets;
+        self.received_chunks.retain(|p, _| tickets.level_at(*p) > 0);
+        self.generated_chunks.retain(|p| tickets.level_at(*p) > 0);
+        for pos in unload {
+            // chunks removed for the limit are not queued again, the generator brings
+            // them back once load levels change and there is room
+            if !self.unload_chunk(pos) {
+                // storage is busy, continue next tick
+                return
+            }
+        }
+    }
+
+    /// Save the chunk at `pos` and its entities if they changed, then remove them from the world.
+    /// Returns false if the chunk could not be saved and has to stay loaded for now.
+    fn unload_chunk(&mut self, pos: ChunkPos) -> bool {
+        let entities = self.entities.ecs.query::<&Position>()
+            .without::<PlayerData>()
+            .iter()
+            .filter(|(_, p)| p.pos.as_chunk() == pos)
+            .map(|(ent, _)| ent)
+            .collect::<Vec<_>>();
+        let dirty = !entities.is_empty()
+            || self.blocks.unsaved_chunks.contains(&pos)
+            || self.entity_chunks.contains(&pos);
+        if dirty {
+            let io = match &self.io {
+                Some(io) => io,
+                // would be lost, chunks that were only generated can be generated again
+                None => return false
+            };
+            match self.saved_chunk(pos) {
+                Ok(Some(saved)) => {
+                    // the I/O worker runs jobs in order, so loading the chunk again reads this save
+                    if io.try_send(WorkerJob::SaveChunk(saved)).is_err() {
+                        return false
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    println!("Failed to save chunk {:?}: {}", pos, e);
+                    return false
+                }
+            }
+        }
+        for ent in entities {
+            self.entities.despawn(ent);
+        }
+        self.blocks.remove_chunk(pos);
+        self.blocks.unsaved_chunks.remove(&pos);
+        self.entity_chunks.remove(&pos);
+        true
+    }
+
+    /// Save everything and archive the world directory once it has been written
+    pub fn backup(&mut self) -> std::io::Result<()> {
+        self.backup_pending = true;
+        self.save_all()
+    }
+
+}
@@ -0,0 +1,157 @@
+use crate::prelude::*;
+
+/// Load levels of the chunks around load sources, such as players and force loaded chunks.
+/// Levels spread outward from the sources, losing one per chunk.
+#[derive(Default)]
+pub struct LoadTickets {
+    /// Levels of the sources at each position, a position can have several
+    sources: HashMap<ChunkPos, Vec<u8>>,
+    /// Chunks with a level above zero
+    levels: HashMap<ChunkPos, ChunkLoadLevel>,
+}
+
+/// The 26 chunks touching `pos`
+fn around(pos: ChunkPos) -> impl Iterator<Item = ChunkPos> {
+    itertools::iproduct!(-1..=1, -1..=1, -1..=1)
+        .filter(|d| *d != (0, 0, 0))
+        .map(move |(x, y, z)| (pos.x + x, pos.y + y, pos.z + z).into())
+}
+
+impl LoadTickets {
+
+    pub fn load_level(&self, pos: ChunkPos) -> ChunkLoadLevel {
+        self.levels.get(&pos).copied().unwrap_or_default()
+    }
+
+    pub fn level_at(&self, pos: ChunkPos) -> u8 {
+        self.load_level(pos).level()
+    }
+
+    pub fn status_at(&self, pos: ChunkPos) -> ChunkStatus {
+        self.load_level(pos).status()
+    }
+
+    /// Chunks with a level above zero
+    pub fn levels(&self) -> &HashMap<ChunkPos, ChunkLoadLevel> {
+        &self.levels
+    }
+
+    /// Add a source of `level` at `pos`
+    pub fn add(&mut self, pos: ChunkPos, level: u8) {
+        self.sources.entry(pos).or_default().push(level);
+        let entry = self.levels.entry(pos).or_default();
+        if level > entry.source {
+            entry.source = level;
+            self.propagate(vec![pos].into());
+        }
+    }
+
+    /// Remove a source of `level` at `pos` added before
+    pub fn remove(&mut self, pos: ChunkPos, level: u8) {
+        let list = match self.sources.get_mut(&pos) {
+            Some(list) => list,
+            None => return
+        };
+        if let Some(i) = list.iter().position(|l| *l == level) {
+            list.swap_remove(i);
+        }
+        let source = list.iter().copied().max().unwrap_or(0);
+        if list.is_empty() {
+            self.sources.remove(&pos);
+        }
+        let entry = self.levels.entry(pos).or_default();
+        if source < entry.source {
+            let old = entry.level();
+            entry.source = source;
+            self.unpropagate(pos, old);
+        }
+    }
+
+    /// Replace all sources with `sources`, changing only the levels of chunks near sources that differ.
+    /// Returns whether any source was added or removed.
+    pub fn set_sources(&mut self, sources: Vec<(ChunkPos, u8)>) -> bool {
+        let mut wanted = HashMap::<(ChunkPos, u8), usize>::new();
+        for source in sources {
+            *wanted.entry(source).or_default() += 1;
+        }
+        let mut current = HashMap::<(ChunkPos, u8), usize>::new();
+        for (pos, levels) in &self.sources {
+            for level in levels {
+                *current.entry((*pos, *level)).or_default() += 1;
+            }
+        }
+        let mut changed = false;
+        // remove first, so moving a source does not propagate its old area again
+        for (&(pos, level), &n) in &current {
+            for _ in wanted.get(&(pos, level)).copied().unwrap_or(0)..n {
+                self.remove(pos, level);
+                changed = true;
+            }
+        }
+        for (&(pos, level), &n) in &wanted {
+            for _ in current.get(&(pos, level)).copied().unwrap_or(0)..n {
+                self.add(pos, level);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Raise the levels around the chunks in `queue` to what they give their neighbours
+    fn propagate(&mut self, mut queue: VecDeque<ChunkPos>) {
+        while let Some(p) = queue.pop_front() {
+            let level = self.level_at(p);
+            if level <= 1 {
+                continue
+            }
+            for n in around(p) {
+                let entry = self.levels.entry(n).or_default();
+                if entry.spread < level - 1 {
+                    let old = entry.level();
+                    entry.spread = level - 1;
+                    if entry.level() != old {
+                        queue.push_back(n);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lower the levels that came from `pos` after its level dropped from `old`
+    fn unpropagate(&mut self, pos: ChunkPos, old: u8) {
+        // clear every level that may have come from `pos`, the ones also given by other sources come back below
+        let mut cleared = vec![pos];
+        let mut queue = VecDeque::new();
+        queue.push_back((pos, old));
+        if let Some(entry) = self.levels.get_mut(&pos) {
+            entry.spread = 0;
+        }
+        while let Some((p, level)) = queue.pop_front() {
+            if level <= 1 {
+                continue
+            }
+            for n in around(p) {
+                if let Some(entry) = self.levels.get_mut(&n) {
+                    if entry.spread != 0 && entry.spread == level - 1 {
+                        entry.spread = 0;
+                        cleared.push(n);
+                        queue.push_back((n, level - 1));
+                    }
+                }
+            }
+        }
+
+        let mut border = VecDeque::new();
+        for p in &cleared {
+            border.push_back(*p);
+            border.extend(around(*p).filter(|n| self.level_at(*n) > 1));
+        }
+        self.propagate(border);
+        for p in cleared {
+            if self.level_at(p) == 0 {
+                self.levels.remove(&p);
+            }
+        }
+    }
+
+}
@@ -25,15 +25,28 @@ impl Updates {
     pub fn add_single_immediate(&mut self, p: BlockPos) {
         self.single.push((Reverse(self.current),p));
     }
+    /// Run the updates that are due. Updates in chunks that are not ticking wait until they are,
+    /// the ones in chunks being unloaded are dropped.
     pub fn update(data: &mut WorldData) {
 
         data.block_updates.current += 1;
+        let mut waiting_area = vec![];
+        let mut waiting_single = vec![];
 
         while let Some((Reverse(c),p)) = data.block_updates.area.pop() {
             if c > data.block_updates.current {
                 data.block_updates.area.push((Reverse(c),p));
                 break
             };
+            match data.tickets.status_at(p.as_chunk()) {
+                // the chunk is being unloaded
+                ChunkStatus::Unloaded => continue,
+                ChunkStatus::Terrain => {
+                    waiting_area.push(p);
+                    continue
+                }
+                _ => {}
+            }
             for pos in Face::iter_all().map(|f| p.shifted(f)) {
                 if let Some(on_update) = data.blocks.block_at(&pos)
                     .and_then(|b| b.behavior.as_ref())
@@ -50,6 +63,14 @@ impl Updates {
                 data.block_updates.single.push((Reverse(c),pos));
                 break
             };
+            match data.tickets.status_at(pos.as_chunk()) {
+                ChunkStatus::Unloaded => continue,
+                ChunkStatus::Terrain => {
+                    waiting_single.push(pos);
+                    continue
+                }
+                _ => {}
+            }
             if let Some(on_update) = data.blocks.block_at(&pos)
                 .and_then(|b| b.behavior.as_ref())
                 .as_ref()
@@ -58,6 +79,13 @@ impl Updates {
                 on_update(pos, data);
             }
         }
-        
+
+        for p in waiting_area {
+            data.block_updates.add_area(p);
+        }
+        for p in waiting_single {
+            data.block_updates.add_single(p);
+        }
+
     }
 }
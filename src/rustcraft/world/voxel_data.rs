@@ -33,50 +33,87 @@ impl<'cnt: 'b, 'b> VoxelData<'cnt> {
     } */
 
     pub fn block_at(&'b self, pos: &impl Coord) -> Option<&'cnt BlockData> {
-        self.chunk_at(pos.as_chunk()).map(|c| c.block_at(pos))
+        let registry = self.registry;
+        self.chunk_at(pos.as_chunk()).and_then(|c| registry.by_id(c.block_at(pos)))
     }
     pub fn block_at_any_state(&'b self, pos: &impl Coord) -> Option<&'cnt BlockData> {
-        self.chunks.get(&pos.as_chunk()).map(|c| c.chunk.block_at(pos))
+        let registry = self.registry;
+        self.chunks.get(&pos.as_chunk()).and_then(|c| registry.by_id(c.chunk.block_at(pos)))
     }
     pub fn set_block_at(&'b mut self, pos: &impl Coord, block: &'cnt BlockData) -> bool {
+        let old = self.block_at(pos);
         let success = self.chunk_at_mut(pos.as_chunk())
             .map(|c| c.set_at(pos, block))
             .unwrap_or(false);
         if success {
+            self.sync_block_entity(pos.as_block(), old, block);
+            self.unsaved_chunks.insert(pos.as_chunk());
+            self.changed_blocks.insert(pos.as_block());
             self.register_mesh_change(pos.as_block());
         }
         success
     }
     pub fn set_block_at_any_state(&mut self, pos: &impl Coord, block: &'cnt BlockData) -> bool {
+        let old = self.block_at_any_state(pos);
         let success = self.chunks.get_mut(&pos.as_chunk())
             .map(|c| c.chunk.set_at(pos, block))
             .unwrap_or(false);
         if success {
+            self.sync_block_entity(pos.as_block(), old, block);
+            self.unsaved_chunks.insert(pos.as_chunk());
             self.changed_chunks.insert(pos.as_chunk());
         }
         success
     }
     pub fn replace_at(&'b mut self, pos: &impl Coord, block: &'cnt BlockData) -> bool {
-        if let Some(c) = self.chunk_at_mut(pos.as_chunk()) {
-            if c.block_at(pos).replacable {
-                c.set_at(pos, block)
-            } else {
-                false
-            }
+        let old = self.block_at(pos);
+        let success = if let Some(c) = self.chunk_at_mut(pos.as_chunk()) {
+            old.map_or(false, |b| b.replacable) && c.set_at(pos, block)
         } else {
             false
+        };
+        if success {
+            self.sync_block_entity(pos.as_block(), old, block);
+            self.unsaved_chunks.insert(pos.as_chunk());
+            self.changed_blocks.insert(pos.as_block());
         }
+        success
     }
     pub fn replace_at_any_state(&mut self, pos: &impl Coord, block: &'cnt BlockData) -> bool {
-        if let Some(c) = self.chunks.get_mut(&pos.as_chunk()) {
-            if c.chunk.block_at(pos).replacable {
-                c.chunk.set_at(pos, block)
-            } else {
-                false
-            }
+        let old = self.block_at_any_state(pos);
+        let success = if let Some(c) = self.chunks.get_mut(&pos.as_chunk()) {
+            old.map_or(false, |b| b.replacable) && c.chunk.set_at(pos, block)
         } else {
             false
+        };
+        if success {
+            self.sync_block_entity(pos.as_block(), old, block);
+            self.unsaved_chunks.insert(pos.as_chunk());
         }
+        success
+    }
+
+    /// Drop the block entity at `pos` if the block `old` was replaced by a different one,
+    /// then create the one `block` carries. Changing only the state keeps the data.
+    fn sync_block_entity(&mut self, pos: BlockPos, old: Option<&'cnt BlockData>, block: &'cnt BlockData) {
+        if old.map_or(false, |old| old.id == block.id) {
+            return
+        }
+        if let Some(c) = self.chunks.get_mut(&pos.as_chunk()) {
+            if let Some(ent) = c.chunk.block_entities.remove(&pos) {
+                self.dropped_block_entities.push((pos, ent));
+            }
+            if let Some(kind) = &block.block_entity {
+                c.chunk.block_entities.insert(pos, kind.create());
+            }
+        }
+    }
+
+    pub fn block_entity(&self, pos: &impl Coord) -> Option<&BlockEntity> {
+        self.chunk_at(pos.as_chunk())?.block_entities.get(&pos.as_block())
+    }
+    pub fn block_entity_mut(&mut self, pos: &impl Coord) -> Option<&mut BlockEntity> {
+        self.chunk_at_mut(pos.as_chunk())?.block_entities.get_mut(&pos.as_block())
     }
 
     pub fn register_mesh_change(&mut self, pos: BlockPos) {
@@ -102,50 +139,78 @@ impl<'cnt: 'b, 'b> VoxelData<'cnt> {
         self.chunk_at_mut(pos.as_chunk()).unwrap().light_at_mut(pos)
     }
 
-    pub fn chunk_at(&self, pos: ChunkPos) -> Option<&Chunk<'cnt>> {
+    /// Add `chunk`, counting it as a neighbour of the chunks around it.
+    /// A chunk already at its position is replaced.
+    pub fn insert_chunk(&mut self, chunk: Box<Chunk>) {
+        let pos = chunk.pos;
+        if let Some(c) = self.chunks.get_mut(&pos) {
+            c.chunk = chunk;
+            return
+        }
+        let mut chunk_data = ChunkData {
+            chunk,
+            loaded_neighbours: 0,
+        };
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let p = (
+                        pos.x+dx,
+                        pos.y+dy,
+                        pos.z+dz,
+                    ).into();
+                    if let Some(c) = self.chunks.get_mut(&p) {
+                        chunk_data.loaded_neighbours += 1;
+                        c.loaded_neighbours += 1;
+                    }
+                }
+            }
+        }
+        self.chunks.insert(pos, chunk_data);
+    }
+
+    /// Remove the chunk at `pos`. Its rendered neighbours go back to being detailed,
+    /// as meshing and lighting need all of a chunk's neighbours.
+    pub fn remove_chunk(&mut self, pos: ChunkPos) -> Option<Box<Chunk>> {
+        let chunk_data = self.chunks.remove(&pos)?;
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let p = (
+                        pos.x+dx,
+                        pos.y+dy,
+                        pos.z+dz,
+                    ).into();
+                    if let Some(c) = self.chunks.get_mut(&p) {
+                        c.loaded_neighbours -= 1;
+                        if c.chunk.chunk_state == ChunkState::Rendered {
+                            c.chunk.chunk_state = ChunkState::Detailed;
+                        }
+                    }
+                }
+            }
+        }
+        self.changed_chunks.remove(&pos);
+        Some(chunk_data.chunk)
+    }
+
+    pub fn chunk_at(&self, pos: ChunkPos) -> Option<&Chunk> {
         self.chunks.get(&pos).filter(|c| c.chunk.chunk_state >= ChunkState::Detailed).map(|cd| cd.chunk.as_ref())
     }
-    pub fn chunk_at_mut(&mut self, pos: ChunkPos) -> Option<&mut Chunk<'cnt>> {
+    pub fn chunk_at_mut(&mut self, pos: ChunkPos) -> Option<&mut Chunk> {
         self.chunks.get_mut(&pos).filter(|c| c.chunk.chunk_state >= ChunkState::Detailed).map(|cd| cd.chunk.as_mut())
     }
 
-    pub fn refresh(&'b mut self, reg: &ItemRegistry, atlas: &TextureAtlas) {
+    /// Recalculate the light of changed chunks, returning the ones whose meshes are out of date
+    pub fn refresh_light(&'b mut self) -> HashSet<ChunkPos> {
         let mut cc = std::mem::take(&mut self.changed_chunks);
-        let mut meshed = HashSet::new();
         cc.retain(|x| self.chunk_at(*x).map(Chunk::renderable).unwrap_or(false));
-        // println!("refresh {:?}",cc);
+        // light gets recalculated for these
+        self.unsaved_chunks.extend(cc.iter().copied());
         for cp in &cc {
             calc_light(*cp, self);
         }
-        // println!("{}",cc.len());
-        for cp in cc {
-            /* for x in -1..=1 {
-                for y in -1..=1 {
-                    for z in -1..=1 { */
-                        let p = cp; /* Vector3 {
-                            x: x + cp.x,
-                            y: y + cp.y,
-                            z: z + cp.z,
-                        }; */
-                        // ! make_mesh is slow, old version faster
-                        // ! need to make another solution here
-                        // ! need to make a hybrid version as well
-                        if !self.chunks.get(&p.into()).unwrap().chunk.renderable() || meshed.contains(&p) {continue}
-                        // println!("{:?}", p);
-                        let (m1,m2) = (meshing::make_mesh(p.into(), self, reg, atlas));
-                        let c = &mut self.chunks.get_mut(&p.into()).unwrap().chunk;
-                        {
-                            let m = c.mesh.as_mut().unwrap();
-                            m.0.update_lit(&m1.0, &m1.1, &m1.2);
-                            m.1.update_lit(&m2.0, &m2.1, &m2.2);
-                        }
-                        c.needs_refresh = false;
-                        c.chunk_state = ChunkState::Rendered;
-                        meshed.insert(p);
-                    /* }
-                }
-            } */
-        }
+        cc
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,69 @@
+use crate::save::{SavedStack, SavedBlockEntity};
+use crate::prelude::*;
+use super::*;
+
+/// A connected player and the server side state of their interactions
+pub struct Client {
+    pub conn: Box<dyn Connection<ServerMsg, ClientMsg>>,
+    /// Cleared once the connection is lost, the client is removed at the end of the tick
+    pub connected: bool,
+    pub name: String,
+    /// The player entity this client controls
+    pub player: hecs::Entity,
+    /// Block being dug and the tick digging started
+    pub digging: Option<(BlockPos, u64)>,
+    /// Stack picked up in the inventory screen
+    pub cursor: Option<ItemStack>,
+    /// Inventory contents as last sent to the client
+    pub sent_inventory: Vec<Option<SavedStack>>,
+    /// Block whose inventory the client has open
+    pub container: Option<BlockPos>,
+    /// Block entity of `container` as last sent to the client
+    pub sent_container: Option<SavedBlockEntity>,
+    /// Chunk the player was last seen in
+    pub chunk: Option<ChunkPos>,
+    /// Chunks the client has been sent and not told to unload
+    pub sent_chunks: HashSet<ChunkPos>,
+    /// Entities the client knows about, with their position and velocity as last sent
+    pub known_entities: HashMap<NetId, (WorldPos, Vector3<f32>)>,
+    /// Seconds of input the client may still send, refilled every tick
+    pub input_budget: f32,
+    /// Sequence number of the last input simulated, and of the last one the client was told about
+    pub last_input: Option<u32>,
+    pub acked_input: Option<u32>,
+}
+
+impl Client {
+
+    pub fn new(conn: Box<dyn Connection<ServerMsg, ClientMsg>>, name: String, player: hecs::Entity) -> Self {
+        Self {
+            conn,
+            connected: true,
+            name,
+            player,
+            digging: None,
+            cursor: None,
+            sent_inventory: vec![],
+            container: None,
+            sent_container: None,
+            chunk: None,
+            sent_chunks: HashSet::new(),
+            known_entities: HashMap::new(),
+            input_budget: 0.,
+            last_input: None,
+            acked_input: None,
+        }
+    }
+
+    /// Send `msg`, marking the client as disconnected if that fails
+    pub fn send(&mut self, msg: ServerMsg) {
+        if !self.connected {
+            return
+        }
+        if let Err(e) = self.conn.send(msg) {
+            println!("Lost connection to {}: {}", self.name, e);
+            self.connected = false;
+        }
+    }
+
+}
@@ -0,0 +1,67 @@
+use crate::prelude::*;
+
+/// Contents of `server.toml`, read by the dedicated server
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ServerConfig {
+    #[serde(default = "port_def")]
+    pub port: u16,
+    #[serde(default = "world_def")]
+    pub world: String,
+    /// Seed used if the world does not exist yet
+    #[serde(default = "seed_def")]
+    pub seed: String,
+    /// Radius in chunks kept loaded around each player
+    #[serde(default = "view_distance_def")]
+    pub view_distance: i32,
+    /// Most chunks kept in memory, see `max_chunks`
+    #[serde(default)]
+    pub max_chunks: Option<usize>,
+    /// Game ticks per second
+    #[serde(default = "tick_rate_def")]
+    pub tick_rate: u32,
+    /// Local port of the remote admin console
+    #[serde(default = "rcon_port_def")]
+    pub rcon_port: u16,
+    /// Password of the remote admin console, which is disabled without one
+    #[serde(default)]
+    pub rcon_password: Option<String>,
+    /// Names of the players allowed to run commands
+    #[serde(default)]
+    pub operators: Vec<String>,
+}
+
+impl ServerConfig {
+    /// Read the config file at `path`, using the defaults if there is none.
+    /// Fails if the file is not valid, naming it in the error.
+    pub fn load(path: &str) -> std::io::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(tomlstr) => toml::from_str(tomlstr.as_ref()).map_err(|e| std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("invalid {}: {}", path, e)
+            )),
+            Err(e) => {
+                println!("Could not read {}: {}, using defaults", path, e);
+                Ok(toml::from_str("").unwrap())
+            }
+        }
+    }
+    /// The configured chunk limit, or one that fits `consts::EXPECTED_PLAYERS` players far apart at the view distance
+    pub fn max_chunks(&self) -> usize {
+        self.max_chunks.unwrap_or_else(|| default_max_chunks(self.view_distance))
+    }
+    pub fn tick_duration(&self) -> Duration {
+        Duration::from_secs(1) / self.tick_rate.max(1)
+    }
+}
+
+const fn port_def() -> u16 {consts::DEFAULT_PORT}
+fn world_def() -> String {consts::DEBUG_WORLD.into()}
+fn seed_def() -> String {consts::DEBUG_SEED.into()}
+const fn view_distance_def() -> i32 {consts::DEFAULT_VIEW_DISTANCE}
+const fn tick_rate_def() -> u32 {20}
+const fn rcon_port_def() -> u16 {consts::DEFAULT_RCON_PORT}
+
+/// Chunks `consts::EXPECTED_PLAYERS` players far apart keep loaded at `view_distance`
+pub fn default_max_chunks(view_distance: i32) -> usize {
+    WorldData::chunks_per_player(view_distance) * consts::EXPECTED_PLAYERS
+}
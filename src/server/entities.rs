@@ -0,0 +1,79 @@
+use crate::prelude::*;
+use super::*;
+
+/// Smallest change in position or velocity that is sent to clients
+const MOVE_EPSILON: f32 = 0.001;
+
+impl<'cnt: 'b, 'b> ServerLoop<'cnt> {
+
+    /// Give every positioned entity without a `NetId` a new one
+    fn assign_net_ids(&mut self) {
+        let new = self.world.entities.ecs.query::<&Position>()
+            .without::<NetId>()
+            .iter()
+            .map(|(ent, _)| ent)
+            .collect::<Vec<_>>();
+        for ent in new {
+            let _ = self.world.entities.ecs.insert_one(ent, NetId(self.next_net_id));
+            self.next_net_id += 1;
+        }
+    }
+
+    /// Tell each client about entities in the chunks it has, which of them moved and which are gone
+    pub fn sync_entities(&mut self) {
+        self.assign_net_ids();
+
+        for client in &mut self.clients {
+            let mut msgs = vec![];
+            let mut moves = vec![];
+            let mut seen = HashSet::new();
+
+            let mut query = self.world.entities.ecs.query::<(&NetId, &Position, Option<&Physics>, Option<&ModelDesc>)>();
+            for (ent, (id, pos, phys, model)) in query.iter() {
+                // the client simulates its own player
+                if ent == client.player || !client.sent_chunks.contains(&pos.pos.as_chunk()) {
+                    continue
+                }
+                seen.insert(*id);
+                let vel = phys.map(Physics::velocity).unwrap_or(Vector3::zero());
+                match client.known_entities.get_mut(id) {
+                    None => {
+                        client.known_entities.insert(*id, (pos.pos, vel));
+                        msgs.push(ServerMsg::EntitySpawn {
+                            id: *id,
+                            pos: pos.clone(),
+                            vel,
+                            model: model.cloned(),
+                        });
+                    }
+                    Some(known) => {
+                        let moved = (pos.pos.0 - known.0.0).magnitude2() > MOVE_EPSILON * MOVE_EPSILON
+                            || (vel - known.1).magnitude2() > MOVE_EPSILON * MOVE_EPSILON;
+                        if moved {
+                            *known = (pos.pos, vel);
+                            moves.push(EntityMove { id: *id, pos: pos.pos, vel });
+                        }
+                    }
+                }
+            }
+            drop(query);
+
+            let gone = client.known_entities.keys()
+                .filter(|id| !seen.contains(id))
+                .copied()
+                .collect::<Vec<_>>();
+            for id in gone {
+                client.known_entities.remove(&id);
+                msgs.push(ServerMsg::EntityDespawn(id));
+            }
+            if !moves.is_empty() {
+                msgs.push(ServerMsg::EntityMoves(moves));
+            }
+
+            for msg in msgs {
+                client.send(msg);
+            }
+        }
+    }
+
+}
@@ -0,0 +1,284 @@
+use crate::cmd::Cmd;
+use crate::save::SavedBlockEntity;
+use crate::prelude::*;
+use super::*;
+
+impl<'cnt: 'b, 'b> ServerLoop<'cnt> {
+
+    /// Handle a message from the client at index `i` of `clients`
+    pub fn handle_msg(&mut self, i: usize, msg: ClientMsg) {
+        let player = self.clients[i].player;
+        match msg {
+            ClientMsg::Inputs(inputs) => {
+                for input in inputs {
+                    self.simulate_input(i, input);
+                }
+            },
+            ClientMsg::DigStart(pos) => {
+                let diggable = self.world.blocks.block_at(&pos)
+                    .map_or(false, |b| b.num_id != self.world.air.num_id);
+                if !diggable {
+                    // broken before the message arrived
+                    return
+                }
+                if !self.in_reach(player, pos) {
+                    return self.reject(i, "dig", pos, "out of reach")
+                }
+                self.clients[i].digging = Some((pos, self.world.ticks));
+            },
+            ClientMsg::DigCancel => {
+                self.clients[i].digging = None;
+            },
+            ClientMsg::DigFinish(pos) => self.finish_dig(i, pos),
+            ClientMsg::Place { pos, against, slot } => self.place(i, pos, against, slot),
+            ClientMsg::ClickSlot(slot) => {
+                let client = &mut self.clients[i];
+                if let Ok(pdata) = self.world.entities.ecs.query_one_mut::<&mut PlayerData>(player) {
+                    if let Some(stack) = pdata.inventory.data.get_mut(slot) {
+                        ItemStack::transfer_or_swap(&mut client.cursor, stack);
+                    }
+                }
+            },
+            ClientMsg::ClickContainerSlot(slot) => {
+                let client = &mut self.clients[i];
+                let pos = match client.container {
+                    Some(pos) => pos,
+                    // closed by the server before the click arrived
+                    None => return
+                };
+                let stack = self.world.blocks.block_entity_mut(&pos)
+                    .and_then(BlockEntity::slots_mut)
+                    .and_then(|slots| slots.get_mut(slot));
+                if let Some(stack) = stack {
+                    ItemStack::transfer_or_swap(&mut client.cursor, stack);
+                    self.world.blocks.unsaved_chunks.insert(pos.as_chunk());
+                }
+            },
+            ClientMsg::CloseInventory => {
+                let client = &mut self.clients[i];
+                client.container = None;
+                if let Ok(pdata) = self.world.entities.ecs.query_one_mut::<&mut PlayerData>(player) {
+                    pdata.inventory.merge(&mut client.cursor);
+                }
+            },
+            ClientMsg::Command(line) => {
+                let name = self.clients[i].name.clone();
+                let output = if !self.operators.contains(&name) {
+                    println!("Refused command {} from {}, who is not an operator", line, name);
+                    "Only operators can run commands".to_owned()
+                } else {
+                    match line.parse::<Cmd>() {
+                        Ok(cmd) => {
+                            println!("{} ran {}", name, line);
+                            let output = cmd.exec(&mut self.world, self.content, Some(&name));
+                            println!("{}", output);
+                            output
+                        }
+                        Err(_) => {
+                            println!("Unknown command {} from {}", line, name);
+                            format!("Unknown command {}", line)
+                        }
+                    }
+                };
+                self.clients[i].send(ServerMsg::CommandOutput(output));
+            },
+            ClientMsg::SetSignText(pos, text) => self.set_sign_text(i, pos, text),
+        }
+    }
+
+    fn reject(&self, i: usize, action: &str, pos: BlockPos, reason: &str) {
+        println!("Rejected {} by {} at {:?}: {}", action, self.clients[i].name, pos, reason);
+    }
+
+    /// Whether `pos` can be reached from the eyes of `player`
+    fn in_reach(&mut self, player: hecs::Entity, pos: BlockPos) -> bool {
+        match self.world.entities.ecs.query_one_mut::<(&Position, &View)>(player) {
+            Ok((p, view)) => {
+                let eyes = p.pos.0 + view.offset();
+                eyes.distance(pos.align_center().0) <= consts::REACH_DISTANCE + consts::BLOCK_HALF_DIAGONAL
+            },
+            Err(_) => false
+        }
+    }
+
+    fn finish_dig(&mut self, i: usize, pos: BlockPos) {
+        let started = match self.clients[i].digging.take() {
+            Some((p, started)) if p == pos => started,
+            _ => return self.reject(i, "dig", pos, "digging a different block")
+        };
+        if !self.in_reach(self.clients[i].player, pos) {
+            return self.reject(i, "dig", pos, "out of reach")
+        }
+        let break_ticks = self.world.blocks.block_at(&pos).map_or(0, BlockData::break_ticks);
+        if self.world.ticks + consts::DIG_TOLERANCE_TICKS < started + break_ticks {
+            return self.reject(i, "dig", pos, "finished too early")
+        }
+        self.break_block(pos);
+    }
+
+    /// Replace the block at `pos` with air, spawning its drop
+    fn break_block(&mut self, pos: BlockPos) {
+        let block = match self.world.blocks.block_at(&pos) {
+            Some(block) => block,
+            None => return
+        };
+        if self.world.blocks.set_block_at(&pos, self.world.air) {
+            if let Some(drop_id) = &block.drops {
+                let stack = ItemStack::of(self.content.items.get(drop_id).clone(), 1);
+                ItemCmp::spawn(&mut self.world, pos.align_center(), stack);
+            }
+            self.world.block_updates.add_area(pos);
+        }
+    }
+
+    fn place(&mut self, i: usize, pos: BlockPos, against: BlockPos, slot: usize) {
+        if slot >= 9 {
+            return self.reject(i, "place", pos, "not a hotbar slot")
+        }
+        let player = self.clients[i].player;
+        // using a block takes precedence over placing against it
+        let on_use = self.world.blocks.block_at(&against)
+            .and_then(|b| b.behavior.as_ref())
+            .and_then(|b| b.on_use);
+        if let Some(f) = on_use {
+            if !self.in_reach(player, against) {
+                return self.reject(i, "use", against, "out of reach")
+            }
+            f(against, &mut self.world);
+            for pos in std::mem::take(&mut self.world.opened_inventories) {
+                self.open_container(i, pos);
+            }
+            return
+        }
+        let held = self.world.entities.ecs.query_one_mut::<&PlayerData>(player).ok()
+            .and_then(|pdata| pdata.inventory.data[slot].as_ref())
+            .map(|stack| stack.item.clone());
+        if let Some(block) = held.as_ref().and_then(ItemLike::as_block) {
+            if !self.in_reach(player, pos) {
+                return self.reject(i, "place", pos, "out of reach")
+            }
+            let block = match self.content.blocks.get(&block.id) {
+                Some(block) => block,
+                None => return self.reject(i, "place", pos, "unknown block")
+            };
+            let face = match Face::iter_all().find(|f| against.shifted(*f) == pos) {
+                Some(face) => face,
+                None => return self.reject(i, "place", pos, "not adjacent")
+            };
+            let heading = self.world.entities.ecs.get::<Position>(player)
+                .map_or(Vector3::new(0., 0., -1.), |p| p.heading());
+            let block = self.content.blocks.placement_state(block, face, heading);
+            if self.world.blocks.replace_at(&pos, block) {
+                self.world.block_updates.add_area(pos);
+                self.world.block_updates.add_single(pos);
+                if let Ok(pdata) = self.world.entities.ecs.query_one_mut::<&mut PlayerData>(player) {
+                    ItemStack::deduct(&mut pdata.inventory.data[slot], 1);
+                }
+            }
+        }
+    }
+
+    /// Show client `i` the inventory of the block entity at `pos` beside their own,
+    /// or let them write on it if it is a sign
+    fn open_container(&mut self, i: usize, pos: BlockPos) {
+        let saved = match self.world.blocks.block_entity(&pos) {
+            Some(ent) => ent.to_saved(),
+            None => return
+        };
+        let client = &mut self.clients[i];
+        if let SavedBlockEntity::Sign(_) = saved {
+            client.send(ServerMsg::BlockEntity(pos, saved));
+            client.send(ServerMsg::EditSign(pos));
+            return
+        }
+        client.container = Some(pos);
+        client.sent_container = Some(saved.clone());
+        client.send(ServerMsg::BlockEntity(pos, saved));
+        client.send(ServerMsg::OpenInventory(pos));
+    }
+
+    fn set_sign_text(&mut self, i: usize, pos: BlockPos, text: String) {
+        if text.chars().count() > consts::MAX_SIGN_TEXT_LENGTH {
+            return self.reject(i, "sign text", pos, "too long")
+        }
+        if !self.in_reach(self.clients[i].player, pos) {
+            return self.reject(i, "sign text", pos, "out of reach")
+        }
+        match self.world.blocks.block_entity_mut(&pos) {
+            Some(BlockEntity::Sign(sign)) => *sign = text.clone(),
+            _ => return self.reject(i, "sign text", pos, "not a sign")
+        }
+        self.world.blocks.unsaved_chunks.insert(pos.as_chunk());
+        // chunks sent later already contain the text
+        for client in &mut self.clients {
+            if client.sent_chunks.contains(&pos.as_chunk()) {
+                client.send(ServerMsg::BlockEntity(pos, SavedBlockEntity::Sign(text.clone())));
+            }
+        }
+    }
+
+    /// Close the block inventory client `i` has open, putting the cursor stack back into their inventory
+    fn close_container(&mut self, i: usize) {
+        let client = &mut self.clients[i];
+        client.container = None;
+        if let Ok(pdata) = self.world.entities.ecs.query_one_mut::<&mut PlayerData>(client.player) {
+            pdata.inventory.merge(&mut client.cursor);
+        }
+        client.send(ServerMsg::CloseInventory);
+    }
+
+    /// Send every client the blocks and inventory contents that changed since the last call
+    pub fn send_changes(&mut self) {
+        // block inventories can only stay open while the block is there and in reach
+        for i in 0..self.clients.len() {
+            if let Some(pos) = self.clients[i].container {
+                if self.world.blocks.block_entity(&pos).is_none() || !self.in_reach(self.clients[i].player, pos) {
+                    self.close_container(i);
+                }
+            }
+        }
+
+        let mut by_chunk: HashMap<ChunkPos, Vec<(BlockPos, BlockId)>> = HashMap::new();
+        for pos in std::mem::take(&mut self.world.blocks.changed_blocks) {
+            if let Some(block) = self.world.blocks.block_at(&pos) {
+                by_chunk.entry(pos.as_chunk()).or_default().push((pos, block.num_id));
+            }
+        }
+
+        for client in &mut self.clients {
+            for (cp, blocks) in &by_chunk {
+                // chunks sent later already contain the change
+                if !client.sent_chunks.contains(cp) {
+                    continue
+                }
+                client.send(if blocks.len() == 1 {
+                    let (pos, id) = blocks[0];
+                    ServerMsg::BlockChanged(pos, id)
+                } else {
+                    ServerMsg::MultiBlockChange(*cp, blocks.clone())
+                });
+            }
+
+            let inventory = self.world.entities.ecs.query_one_mut::<&PlayerData>(client.player).ok()
+                .map(|pdata| pdata.inventory.data.iter()
+                    .map(|stack| stack.as_ref().map(ItemStack::to_saved))
+                    .collect::<Vec<_>>()
+                );
+            if let Some(inventory) = inventory {
+                if client.sent_inventory != inventory {
+                    client.sent_inventory = inventory.clone();
+                    client.send(ServerMsg::Inventory(inventory));
+                }
+            }
+
+            if let Some(pos) = client.container {
+                let saved = self.world.blocks.block_entity(&pos).map(BlockEntity::to_saved);
+                if saved.is_some() && client.sent_container != saved {
+                    client.sent_container = saved.clone();
+                    client.send(ServerMsg::BlockEntity(pos, saved.unwrap()));
+                }
+            }
+        }
+    }
+
+}
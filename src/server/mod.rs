@@ -1,28 +1,72 @@
 
 use crate::updates::Updates;
+use crate::save::LevelData;
 use crate::prelude::*;
 
 mod msgs;
+mod net;
+mod config;
+mod client;
+mod interaction;
+mod stream;
+mod entities;
+mod movement;
+mod replay;
+pub mod rcon;
 pub use msgs::*;
+pub use net::*;
+pub use config::*;
+pub use client::*;
+pub use stream::{encode_chunk, decode_chunk};
+pub use replay::*;
 
 pub struct ServerLoop<'cnt> {
     pub content: &'cnt Content,
     pub world: WorldData<'cnt>,
     pub last_tick: Instant,
     pub last_tick_dur: f32,
-    pub rx: Receiver<ClientMsg>,
-    pub tx: Sender<ServerMsg>,
+    pub clients: Vec<Client>,
+    pub tick_duration: Duration,
+    /// Radius in chunks kept loaded around the player
+    pub view_distance: i32,
+    /// Most chunks kept loaded, the least needed ones are unloaded beyond that
+    pub max_chunks: usize,
+    /// Names of the players allowed to run commands
+    pub operators: Vec<String>,
+    pub next_net_id: u32,
+    /// Connections that completed the handshake, joining on the next tick
+    pub joining: Vec<(Box<dyn Connection<ServerMsg, ClientMsg>>, String)>,
+    /// Console commands to run on the next tick, with where to send their output
+    pub commands: Vec<(String, Option<mpsc::Sender<String>>)>,
+    /// Output destinations of the commands collected this tick, in order
+    command_replies: VecDeque<Option<mpsc::Sender<String>>>,
+    pub recorder: Option<Recorder>,
+    pub playback: Option<Playback>,
+    /// Events of the current tick taken from `playback`
+    pub replayed: Vec<ReplayEvent>,
 }
 
 impl<'cnt: 'b, 'b> ServerLoop<'cnt> {
 
-    pub fn new(conn: (Sender<ServerMsg>, Receiver<ClientMsg>), content: &'cnt Content) -> Self {
+    pub fn new(content: &'cnt Content, world_name: &str) -> Self {
             
-        let world = WorldData::new(consts::DEBUG_SEED, content.blocks.get("air").unwrap());
-        let (tx, rx) = conn;
+        let world = WorldData::open(world_name, content).unwrap_or_else(|e| {
+            println!("Could not open world {}: {}", world_name, e);
+            WorldData::new(LevelData::new(consts::DEBUG_SEED, content), &content.blocks)
+        });
         Self {
-            tx,
-            rx,
+            clients: vec![],
+            tick_duration: consts::TICK_DURATION,
+            view_distance: consts::DEFAULT_VIEW_DISTANCE,
+            max_chunks: default_max_chunks(consts::DEFAULT_VIEW_DISTANCE),
+            operators: vec![],
+            next_net_id: 0,
+            joining: vec![],
+            commands: vec![],
+            command_replies: VecDeque::new(),
+            recorder: None,
+            playback: None,
+            replayed: vec![],
             content,
             world,
             last_tick: Instant::now(),
@@ -34,7 +78,7 @@ impl<'cnt: 'b, 'b> ServerLoop<'cnt> {
     pub fn run_and_sleep(&'b mut self) -> bool {
         let ret = self.run();
         if ret {
-            let sleep: Duration = (self.last_tick + consts::TICK_DURATION) - Instant::now();
+            let sleep: Duration = (self.last_tick + self.tick_duration).saturating_duration_since(Instant::now());
             if sleep.as_nanos() > 0 {
                 std::thread::sleep(sleep)
             }
@@ -43,75 +87,231 @@ impl<'cnt: 'b, 'b> ServerLoop<'cnt> {
     }
 
     pub fn run(&'b mut self) -> bool {
+        if self.last_tick.elapsed() < self.tick_duration {
+            return true
+        }
+        self.last_tick += self.tick_duration;
+        let start = Instant::now();
+        self.tick();
+        self.last_tick_dur = start.elapsed().as_secs_f32() * 1000.;
+        true
+    }
+
+    /// Advance the world by one tick and exchange messages with the clients.
+    /// Everything that changes the world happens here, so a replay only has to repeat the same ticks.
+    pub fn tick(&mut self) {
+
+        self.world.update_tickets(self.view_distance);
 
         // GAME TICK
-        if let Some(dur) = self.handle_game_tick() {
-            self.last_tick_dur = dur.as_secs_f32() * 1000.;
-        }
+        self.handle_game_tick();
 
         // ! START SYSTEMS
-        WanderingAI::system_update(&mut self.world, self.last_tick_dur);
-        Physics::system_update(&mut self.world, self.last_tick_dur);
+        let delta = self.tick_duration.as_secs_f32();
+        WanderingAI::system_update(&mut self.world, delta);
+        Physics::system_update(&mut self.world, delta);
         FallingBlock::system_collide_land(&mut self.world);
         // ! STOP SYSTEMS
 
-        // TODO this is too slow
-        //self.world.load(&self.content, 5); // ! load without meshing
+        self.world.load(self.content, consts::LOAD_WORK_PER_TICK, self.max_chunks);
+        self.world.unload_chunks(self.max_chunks);
+        for pos in std::mem::take(&mut self.world.taken_chunks) {
+            self.record(&ReplayEvent::ChunkRead(pos));
+        }
+        self.world.blocks.refresh_light();
 
+        self.world.poll_io();
         self.handle_msgs();
+        self.send_player_states();
+        self.send_changes();
+        self.stream_chunks();
+        self.sync_entities();
 
-        true
+    }
 
+    /// Have `conn` join as `name` once the player's saved data is read
+    pub fn connect(&mut self, conn: Box<dyn Connection<ServerMsg, ClientMsg>>, name: &str) {
+        self.joining.push((conn, name.to_owned()));
     }
 
-    pub fn handle_game_tick(&'b mut self) -> Option<Duration> {
-        if self.last_tick.elapsed() > consts::TICK_DURATION {
-            self.last_tick += consts::TICK_DURATION;
+    /// Run a console command on the next tick, sending its output to `reply` or printing it
+    pub fn command(&mut self, line: &str, reply: Option<mpsc::Sender<String>>) {
+        self.commands.push((line.to_owned(), reply));
+    }
 
-            let start = Instant::now();
-            
-            Updates::update(&mut self.world);
-            self.world.ticks += 1;
-
-            component::ItemCmp::system_tick_age_items(&mut self.world);
-            component::PathFinding::system_update_tick(&mut self.world);
-    
-            let mut rng = rand::thread_rng();
-            use rand::prelude::*;
-    
-            let keys = self.world.blocks.chunks.iter()
-                .filter(|(_,c)|
-                    c.all_neighbours_loaded() &&
-                    c.chunk.renderable()
-                )
-                .map(|(k,_)| k.clone())
-                .collect::<Vec<_>>();
-            for cp in keys {
-                // println!("{:?}",cp);
-                for _ in 0..consts::RANDOM_TICK_SPEED {
-                    let random = rng.gen::<(i32,i32,i32)>();
-                    let pos = cp.as_block() + Vector3::from(random).map(|x| x.abs() % 16).into();
-                    if let Some(on_rnd_tick) = self.world.blocks.block_at(&pos).and_then(|b| b.behavior.as_ref()).and_then(|beh| beh.on_rnd_tick.as_ref()) {
-                        on_rnd_tick(pos, &mut self.world)
-                    }
+    /// Add a client playing as `name`, replacing any client already using that name
+    fn join(&mut self, conn: Box<dyn Connection<ServerMsg, ClientMsg>>, name: &str) {
+        if let Some(i) = self.clients.iter().position(|c| c.name == name) {
+            // keeps the player entity, which is taken over by the new connection
+            println!("{} connected again, dropping the old connection", name);
+            let mut old = self.clients.remove(i);
+            if let Ok(pdata) = self.world.entities.ecs.query_one_mut::<&mut PlayerData>(old.player) {
+                pdata.inventory.merge(&mut old.cursor);
+            }
+        }
+        let player = match self.world.join(name, &self.content.items) {
+            Ok(player) => player,
+            Err(e) => return println!("Could not load player {}: {}", name, e),
+        };
+        let pos = self.world.entities.ecs.get::<Position>(player)
+            .map(|p| p.pos)
+            .unwrap_or(self.world.level.spawn);
+        let mut client = Client::new(conn, name.to_owned(), player);
+        client.chunk = Some(pos.as_chunk());
+        client.send(ServerMsg::Spawn {
+            pos,
+            ticks: self.world.ticks,
+            day_time: self.world.day_time,
+        });
+        println!("{} joined", name);
+        self.clients.push(client);
+    }
+
+    /// Remove the clients whose connection was lost, saving their players
+    fn drop_disconnected(&mut self) {
+        let (gone, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.clients)
+            .into_iter()
+            .partition(|c| !c.connected);
+        self.clients = kept;
+        for mut client in gone {
+            self.record(&ReplayEvent::Leave(client.name.clone()));
+            if let Ok(pdata) = self.world.entities.ecs.query_one_mut::<&mut PlayerData>(client.player) {
+                pdata.inventory.merge(&mut client.cursor);
+            }
+            if let Err(e) = self.world.leave(&client.name) {
+                println!("Failed to save player {}: {}", client.name, e);
+            }
+            println!("{} left", client.name);
+        }
+    }
+
+    /// Wait for all pending chunk I/O to reach the disk
+    pub fn shutdown(&mut self) {
+        if let Err(e) = self.world.close() {
+            println!("Failed to flush world: {}", e);
+        }
+    }
+
+    pub fn handle_game_tick(&mut self) {
+
+        Updates::update(&mut self.world);
+        self.world.advance_tick();
+        self.world.autosave();
+
+        component::ItemCmp::system_tick_age_items(&mut self.world);
+        BlockEntity::system_spill_dropped(&mut self.world);
+        BlockEntity::system_tick(&mut self.world, self.content);
+        component::PathFinding::system_update_tick(&mut self.world);
+
+        use rand::prelude::*;
+
+        let mut keys = self.world.blocks.chunks.iter()
+            .filter(|(k,c)|
+                c.all_neighbours_loaded() &&
+                c.chunk.renderable() &&
+                self.world.tickets.status_at(**k) >= ChunkStatus::Ticking
+            )
+            .map(|(k,_)| k.clone())
+            .collect::<Vec<_>>();
+        // the map's order differs between runs, the random numbers have to be drawn in the same order
+        keys.sort_by_key(|k| k.as_tuple());
+        for cp in keys {
+            // println!("{:?}",cp);
+            for _ in 0..consts::RANDOM_TICK_SPEED {
+                let random = self.world.rng.gen::<(i32,i32,i32)>();
+                let pos = cp.as_block() + Vector3::from(random).map(|x| x.abs() % 16).into();
+                if let Some(on_rnd_tick) = self.world.blocks.block_at(&pos).and_then(|b| b.behavior.as_ref()).and_then(|beh| beh.on_rnd_tick.as_ref()) {
+                    on_rnd_tick(pos, &mut self.world)
                 }
             }
-            Some(start.elapsed())
-        } else {
-            None
         }
+
     }
 
+    /// Handle joins, client messages, console commands and leaves, in that order.
+    /// When playing back a replay they come from the recording instead.
     pub fn handle_msgs(&'b mut self) {
 
-        for msg in self.rx.try_iter() {
-            match msg {
-                ClientMsg::LoadAround(p) => {
-                    self.world.load_around(&p);
+        let tick_secs = self.tick_duration.as_secs_f32();
+        for client in &mut self.clients {
+            client.input_budget = (client.input_budget + tick_secs).min(consts::MAX_INPUT_BACKLOG);
+        }
+
+        let events = if self.playback.is_some() {
+            std::mem::take(&mut self.replayed)
+        } else {
+            self.collect_events()
+        };
+        for event in events {
+            self.record(&event);
+            self.apply_event(event);
+        }
+        self.drop_disconnected();
+
+    }
+
+    fn collect_events(&mut self) -> Vec<ReplayEvent> {
+        let mut events = vec![];
+        for (_, name) in &self.joining {
+            // players join once their saved data has been read
+            if self.world.request_player(name) {
+                events.push(ReplayEvent::Join(name.clone()));
+            }
+        }
+        for client in &mut self.clients {
+            loop {
+                if !client.connected {
+                    break
+                }
+                match client.conn.try_recv() {
+                    Ok(Some(msg)) => events.push(ReplayEvent::Msg(client.name.clone(), msg)),
+                    Ok(None) => break,
+                    Err(e) => {
+                        println!("Lost connection to {}: {}", client.name, e);
+                        client.connected = false;
+                    }
                 }
             }
         }
+        for (line, reply) in self.commands.drain(..) {
+            events.push(ReplayEvent::Command(line));
+            self.command_replies.push_back(reply);
+        }
+        events
+    }
 
+    fn apply_event(&mut self, event: ReplayEvent) {
+        match event {
+            ReplayEvent::Join(name) => {
+                let conn = match self.joining.iter().position(|(_, n)| *n == name) {
+                    Some(i) => self.joining.remove(i).0,
+                    None => box NullConnection,
+                };
+                self.join(conn, &name);
+            }
+            ReplayEvent::Msg(name, msg) => {
+                if let Some(i) = self.clients.iter().position(|c| c.name == name) {
+                    self.handle_msg(i, msg);
+                }
+            }
+            ReplayEvent::Command(line) => {
+                let output = match line.parse::<crate::cmd::Cmd>() {
+                    Ok(cmd) => cmd.exec(&mut self.world, self.content, None),
+                    Err(_) => format!("Unknown command {}", line),
+                };
+                // replayed commands have nobody waiting for them
+                match self.command_replies.pop_front().flatten() {
+                    Some(reply) => { let _ = reply.send(output); }
+                    None => println!("{}", output),
+                }
+            }
+            ReplayEvent::Leave(name) => {
+                if let Some(client) = self.clients.iter_mut().find(|c| c.name == name) {
+                    client.connected = false;
+                }
+            }
+            ReplayEvent::ChunkRead(_) | ReplayEvent::End(_) => {}
+        }
     }
 
 }
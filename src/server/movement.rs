@@ -0,0 +1,49 @@
+use crate::prelude::*;
+use super::*;
+
+impl<'cnt: 'b, 'b> ServerLoop<'cnt> {
+
+    /// Move the player of client `i` by one of its inputs, the same way the client predicted it
+    pub fn simulate_input(&mut self, i: usize, input: PlayerInput) {
+        let client = &mut self.clients[i];
+        // acknowledged even if rejected, so the client rewinds to where the server has the player
+        client.last_input = Some(input.seq);
+        if !input.is_valid() {
+            return println!("Rejected input {} by {}: invalid values", input.seq, client.name)
+        }
+        if input.delta > client.input_budget {
+            return println!("Rejected input {} by {}: moving faster than real time", input.seq, client.name)
+        }
+        client.input_budget -= input.delta;
+
+        let player = client.player;
+        let pos = match self.world.entities.ecs.query_one_mut::<(&mut Position, &mut Physics)>(player) {
+            Ok((pos, phys)) => {
+                input.apply(pos, phys, &self.world.blocks);
+                self.world.entities.tree.update(player, &pos.get_aabb());
+                pos.pos
+            }
+            Err(_) => return
+        };
+        self.clients[i].chunk = Some(pos.as_chunk());
+    }
+
+    /// Tell clients where their player is after the last input simulated for them
+    pub fn send_player_states(&mut self) {
+        for client in &mut self.clients {
+            let seq = match client.last_input {
+                Some(seq) if client.acked_input != Some(seq) => seq,
+                _ => continue
+            };
+            if let Ok((pos, phys)) = self.world.entities.ecs.query_one_mut::<(&Position, &Physics)>(client.player) {
+                client.acked_input = Some(seq);
+                client.send(ServerMsg::PlayerState {
+                    seq,
+                    pos: pos.pos,
+                    phys: phys.clone(),
+                });
+            }
+        }
+    }
+
+}
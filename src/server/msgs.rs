@@ -1,10 +1,84 @@
 
+use crate::save::{SavedStack, SavedBlockEntity};
 use crate::prelude::*;
 
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub enum ServerMsg {
-    
+    /// Sent once after connecting, with the player position and time of day
+    Spawn {
+        pos: WorldPos,
+        ticks: u64,
+        day_time: u64,
+    },
+    /// Chunk data as produced by `encode_chunk`
+    ChunkData(ChunkPos, Vec<u8>),
+    /// The chunk left the view distance and can be dropped
+    UnloadChunk(ChunkPos),
+    /// A single block changed, given by the numeric id of its state
+    BlockChanged(BlockPos, BlockId),
+    /// Several blocks changed within one chunk
+    MultiBlockChange(ChunkPos, Vec<(BlockPos, BlockId)>),
+    /// Contents of the player's inventory, sent whenever they change
+    Inventory(Vec<Option<SavedStack>>),
+    /// Block entity data of a block whose inventory the player has open, sent whenever it changes,
+    /// or of a sign whose text changed
+    BlockEntity(BlockPos, SavedBlockEntity),
+    /// Show the inventory of the block at this position beside the player's
+    OpenInventory(BlockPos),
+    /// The open block inventory was closed by the server, because the block is gone or out of reach
+    CloseInventory,
+    /// Let the player write the text of the sign at this position
+    EditSign(BlockPos),
+    /// What a command the player ran printed
+    CommandOutput(String),
+    /// An entity came into view
+    EntitySpawn {
+        id: NetId,
+        pos: Position,
+        vel: Vector3<f32>,
+        model: Option<ModelDesc>,
+    },
+    /// An entity was removed or went out of view
+    EntityDespawn(NetId),
+    /// Entities that moved since the last tick
+    EntityMoves(Vec<EntityMove>),
+    /// Where the player ended up after the server simulated input `seq`
+    PlayerState {
+        seq: u32,
+        pos: WorldPos,
+        phys: Physics,
+    },
 }
 
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct EntityMove {
+    pub id: NetId,
+    pub pos: WorldPos,
+    pub vel: Vector3<f32>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub enum ClientMsg {
-    LoadAround(ChunkPos),
+    /// Movement of the frames since the last tick, oldest first
+    Inputs(Vec<PlayerInput>),
+    DigStart(BlockPos),
+    DigCancel,
+    DigFinish(BlockPos),
+    /// Use the block `against` if it has a use,
+    /// otherwise place the block held in hotbar `slot` at `pos`
+    Place {
+        pos: BlockPos,
+        against: BlockPos,
+        slot: usize,
+    },
+    /// Click an inventory slot, moving items between it and the cursor
+    ClickSlot(usize),
+    /// Click a slot of the open block inventory
+    ClickContainerSlot(usize),
+    /// Put whatever is held by the cursor back into the inventory and close the block inventory, if any
+    CloseInventory,
+    /// Chat line to be run as a command, by operators only
+    Command(String),
+    /// New text of the sign at this position
+    SetSignText(BlockPos, String),
 }
@@ -0,0 +1,252 @@
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::net::{TcpStream, ToSocketAddrs};
+use mpsc::{Sender, SyncSender, Receiver, TryRecvError, TrySendError, channel, sync_channel};
+use serde::{Serialize, de::DeserializeOwned};
+use crate::save::ModInfo;
+use crate::prelude::*;
+use super::*;
+
+/// Bumped whenever `ClientMsg` or `ServerMsg` change
+pub const PROTOCOL_VERSION: u32 = 13;
+
+/// Two-way message connection, sending `S` and receiving `R`
+pub trait Connection<S, R>: Send {
+    fn send(&mut self, msg: S) -> io::Result<()>;
+    /// Next received message without blocking, an error once the other side is gone
+    fn try_recv(&mut self) -> io::Result<Option<R>>;
+}
+
+fn disconnected() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionAborted, "disconnected")
+}
+
+fn invalid(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Connection between threads of the same process
+pub struct ChannelConnection<S, R> {
+    tx: Sender<S>,
+    rx: Receiver<R>,
+}
+
+/// Both ends of an in-process connection
+pub fn channel_pair<A, B>() -> (ChannelConnection<A, B>, ChannelConnection<B, A>) {
+    let (atx, brx) = channel();
+    let (btx, arx) = channel();
+    (
+        ChannelConnection { tx: atx, rx: arx },
+        ChannelConnection { tx: btx, rx: brx },
+    )
+}
+
+impl<S: Send, R: Send> Connection<S, R> for ChannelConnection<S, R> {
+    fn send(&mut self, msg: S) -> io::Result<()> {
+        self.tx.send(msg).map_err(|_| disconnected())
+    }
+    fn try_recv(&mut self) -> io::Result<Option<R>> {
+        match self.rx.try_recv() {
+            Ok(msg) => Ok(Some(msg)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(disconnected()),
+        }
+    }
+}
+
+/// `msg` prefixed with its length as a little endian u32
+fn encode_frame(msg: &impl Serialize) -> io::Result<Vec<u8>> {
+    let len = bincode::serialized_size(msg).map_err(invalid)? as usize;
+    if len > consts::MAX_PACKET_SIZE {
+        return Err(invalid(format!("packet of {} bytes is too large", len)))
+    }
+    let mut bytes = Vec::with_capacity(len + 4);
+    bytes.extend_from_slice(&(len as u32).to_le_bytes());
+    bincode::serialize_into(&mut bytes, msg).map_err(invalid)?;
+    Ok(bytes)
+}
+
+fn write_frame(w: &mut impl Write, msg: &impl Serialize) -> io::Result<()> {
+    w.write_all(&encode_frame(msg)?)
+}
+
+fn read_frame<T: DeserializeOwned>(r: &mut impl Read) -> io::Result<T> {
+    let mut len = [0; 4];
+    r.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > consts::MAX_PACKET_SIZE {
+        return Err(invalid(format!("packet of {} bytes is too large", len)))
+    }
+    let mut bytes = vec![0; len];
+    r.read_exact(&mut bytes)?;
+    bincode::deserialize(&bytes).map_err(invalid)
+}
+
+/// Connection over TCP, sending length-prefixed bincode messages.
+/// Incoming messages are read and outgoing ones written on separate threads, so a slow peer never blocks the caller.
+/// It is dropped once `consts::SEND_QUEUE_LENGTH` messages wait to be written or a write takes longer than `consts::WRITE_TIMEOUT`.
+pub struct TcpConnection<S, R> {
+    stream: TcpStream,
+    rx: Receiver<io::Result<R>>,
+    tx: SyncSender<Vec<u8>>,
+    _send: PhantomData<fn(S)>,
+}
+
+impl<S: Serialize, R: DeserializeOwned + Send + 'static> TcpConnection<S, R> {
+
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(consts::WRITE_TIMEOUT))?;
+        let mut reader = io::BufReader::new(stream.try_clone()?);
+        let (tx, rx) = channel();
+        std::thread::spawn(move || loop {
+            let msg = read_frame(&mut reader);
+            let failed = msg.is_err();
+            if tx.send(msg).is_err() || failed {
+                break
+            }
+        });
+        let mut writer = stream.try_clone()?;
+        let (frame_tx, frame_rx) = sync_channel::<Vec<u8>>(consts::SEND_QUEUE_LENGTH);
+        std::thread::spawn(move || {
+            for frame in frame_rx {
+                if writer.write_all(&frame).is_err() {
+                    // also ends the reader, so the connection is seen as lost
+                    let _ = writer.shutdown(std::net::Shutdown::Both);
+                    break
+                }
+            }
+        });
+        Ok(Self {
+            stream,
+            rx,
+            tx: frame_tx,
+            _send: PhantomData,
+        })
+    }
+
+    pub fn peer_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+}
+
+impl<S: Serialize, R: DeserializeOwned + Send> Connection<S, R> for TcpConnection<S, R> {
+    fn send(&mut self, msg: S) -> io::Result<()> {
+        match self.tx.try_send(encode_frame(&msg)?) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                let _ = self.stream.shutdown(std::net::Shutdown::Both);
+                Err(io::Error::new(io::ErrorKind::TimedOut, "too many messages waiting to be sent"))
+            }
+            // the writer stopped after a failed or timed out write
+            Err(TrySendError::Disconnected(_)) => Err(disconnected()),
+        }
+    }
+    fn try_recv(&mut self) -> io::Result<Option<R>> {
+        match self.rx.try_recv() {
+            Ok(msg) => msg.map(Some),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(disconnected()),
+        }
+    }
+}
+
+impl<S, R> Drop for TcpConnection<S, R> {
+    fn drop(&mut self) {
+        // stops the reader and writer threads
+        let _ = self.stream.shutdown(std::net::Shutdown::Both);
+    }
+}
+
+/// Identifies the content a side of the connection was built with
+#[derive(PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct ModHash {
+    name: String,
+    version: String,
+    hash: u64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+enum Welcome {
+    /// With the server's block ids, which block changes refer to
+    Accepted(BlockIdMap),
+    Rejected(String),
+}
+
+fn mod_hashes(mods: &[ModInfo]) -> Vec<ModHash> {
+    mods.iter()
+        .map(|m| ModHash {
+            name: m.name.clone(),
+            version: m.version.clone(),
+            hash: m.hash,
+        })
+        .collect()
+}
+
+/// Reason to refuse a client with mods `theirs`, if any
+fn compare_mods(ours: &[ModHash], theirs: &[ModHash]) -> Option<String> {
+    for m in ours {
+        match theirs.iter().find(|t| t.name == m.name) {
+            None => return Some(format!("missing mod {}", m.name)),
+            Some(t) if t.version != m.version => return Some(format!(
+                "mod {} has version {}, server has {}", m.name, t.version, m.version
+            )),
+            Some(t) if t.hash != m.hash => return Some(format!("mod {} has different content", m.name)),
+            _ => {}
+        }
+    }
+    theirs.iter()
+        .find(|t| !ours.iter().any(|m| m.name == t.name))
+        .map(|t| format!("server does not have mod {}", t.name))
+}
+
+/// Reason to refuse the player name `name`, if any. Names are used as file names.
+pub fn check_name(name: &str) -> Option<String> {
+    if name.is_empty() || name.len() > consts::MAX_NAME_LENGTH {
+        Some(format!("name must be 1 to {} characters long", consts::MAX_NAME_LENGTH))
+    } else if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        Some(format!("name {} may only contain letters, digits and underscores", name))
+    } else {
+        None
+    }
+}
+
+/// Connect to a server as the player `name` with content made of `mods` and do the client side of the handshake.
+/// Returns the server's block ids, which content has to be finished with before handling messages.
+pub fn connect(addr: impl ToSocketAddrs, name: &str, mods: &[ModInfo]) -> io::Result<(TcpConnection<ClientMsg, ServerMsg>, BlockIdMap)> {
+    let mut stream = TcpStream::connect(addr)?;
+    stream.set_read_timeout(Some(consts::HANDSHAKE_TIMEOUT))?;
+    // the version is sent on its own, so the rest of the handshake may change with it
+    write_frame(&mut stream, &PROTOCOL_VERSION)?;
+    write_frame(&mut stream, &mod_hashes(mods))?;
+    write_frame(&mut stream, &name)?;
+    match read_frame(&mut stream)? {
+        Welcome::Accepted(block_ids) => {
+            stream.set_read_timeout(None)?;
+            Ok((TcpConnection::new(stream)?, block_ids))
+        }
+        Welcome::Rejected(reason) => Err(io::Error::new(io::ErrorKind::ConnectionRefused, reason)),
+    }
+}
+
+/// Do the server side of the handshake with a newly connected client, returning its player name
+pub fn accept(mut stream: TcpStream, content: &Content) -> io::Result<(TcpConnection<ServerMsg, ClientMsg>, String)> {
+    stream.set_read_timeout(Some(consts::HANDSHAKE_TIMEOUT))?;
+    let protocol: u32 = read_frame(&mut stream)?;
+    let mut name = String::new();
+    let rejection = if protocol != PROTOCOL_VERSION {
+        Some(format!("protocol version {}, server has {}", protocol, PROTOCOL_VERSION))
+    } else {
+        let theirs: Vec<ModHash> = read_frame(&mut stream)?;
+        name = read_frame(&mut stream)?;
+        compare_mods(&mod_hashes(&content.mods), &theirs).or_else(|| check_name(&name))
+    };
+    if let Some(reason) = rejection {
+        write_frame(&mut stream, &Welcome::Rejected(reason.clone()))?;
+        return Err(io::Error::new(io::ErrorKind::ConnectionRefused, reason))
+    }
+    write_frame(&mut stream, &Welcome::Accepted(content.blocks.id_map().clone()))?;
+    stream.set_read_timeout(None)?;
+    Ok((TcpConnection::new(stream)?, name))
+}
@@ -0,0 +1,83 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use crate::prelude::*;
+
+/// A command line together with where its output goes
+pub type RconCommand = (String, mpsc::Sender<String>);
+
+/// Accept admin connections on `port` of the loopback interface.
+/// Clients send lines of text: the first is `password`, answered with `Authenticated` or `Wrong password`,
+/// every line after that is a command, answered with one line of output once the server ran it.
+pub fn listen(port: u16, password: String) -> io::Result<mpsc::Receiver<RconCommand>> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    let (tx, rx) = mpsc::channel();
+    let password = Arc::new(password);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    println!("Failed to accept admin connection: {}", e);
+                    continue
+                }
+            };
+            let tx = tx.clone();
+            let password = password.clone();
+            std::thread::spawn(move || {
+                let addr = stream.peer_addr();
+                if let Err(e) = serve(stream, &password, tx) {
+                    println!("Admin connection {:?} failed: {}", addr, e);
+                }
+            });
+        }
+    });
+    Ok(rx)
+}
+
+/// Read one line of at most `MAX_RCON_LINE` bytes, `None` at the end of the stream.
+/// Longer lines are an error, which closes the connection.
+fn read_line(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    let read = reader.take(consts::MAX_RCON_LINE as u64).read_line(&mut line)?;
+    if read == 0 {
+        return Ok(None)
+    }
+    if read == consts::MAX_RCON_LINE && !line.ends_with('\n') {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("line longer than {} bytes", consts::MAX_RCON_LINE)))
+    }
+    Ok(Some(line.trim().to_owned()))
+}
+
+fn serve(mut stream: TcpStream, password: &str, commands: mpsc::Sender<RconCommand>) -> io::Result<()> {
+    let addr = stream.peer_addr()?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    stream.set_read_timeout(Some(consts::HANDSHAKE_TIMEOUT))?;
+    let given = read_line(&mut reader)?.unwrap_or_default();
+    if given != password {
+        println!("Refused admin connection {}: wrong password", addr);
+        // slows down guessing
+        std::thread::sleep(Duration::from_secs(1));
+        return writeln!(stream, "Wrong password")
+    }
+    stream.set_read_timeout(None)?;
+    writeln!(stream, "Authenticated")?;
+    println!("Admin connected from {}", addr);
+
+    while let Some(line) = read_line(&mut reader)? {
+        if line.is_empty() {
+            continue
+        }
+        let (reply_tx, reply_rx) = mpsc::channel();
+        if commands.send((line.clone(), reply_tx)).is_err() {
+            // the server stopped
+            break
+        }
+        let output = reply_rx.recv_timeout(consts::RCON_REPLY_TIMEOUT)
+            .unwrap_or_else(|_| format!("No answer from the server for {}", line));
+        // keep the answer to one line
+        writeln!(stream, "{}", output.replace('\n', " "))?;
+    }
+    println!("Admin disconnected from {}", addr);
+    Ok(())
+}
@@ -0,0 +1,286 @@
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::fs::File;
+use std::hash::Hasher;
+use std::path::Path;
+use crate::save::Backup;
+use crate::prelude::*;
+use super::*;
+
+const REPLAY_MAGIC: &[u8; 4] = b"RCRP";
+
+/// Something the server got from outside the simulation, recorded with the tick it was handled in
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub enum ReplayEvent {
+    Join(String),
+    Msg(String, ClientMsg),
+    /// Console command
+    Command(String),
+    Leave(String),
+    /// A chunk read from storage or generated was used by loading
+    ChunkRead(ChunkPos),
+    /// Recording stopped, with the checksum of the world at that point
+    End(u64),
+}
+
+/// Start of a replay file, followed by `(tick, ReplayEvent)` pairs
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ReplayHeader {
+    pub tick_duration: Duration,
+    pub view_distance: i32,
+    pub max_chunks: usize,
+    pub operators: Vec<String>,
+    /// World directory when recording started
+    pub world: Backup,
+}
+
+fn invalid(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Hash of the loaded blocks, the entity positions and the random number generator,
+/// to tell whether a replay ended up with the same world as its recording
+pub fn world_checksum(world: &WorldData) -> u64 {
+    // stable across processes and builds, unlike `DefaultHasher`
+    let mut hasher = crate::util::Fnv64::default();
+    hasher.write_u64(world.ticks);
+    let mut chunks = world.blocks.chunks.keys().copied().collect::<Vec<_>>();
+    chunks.sort_by_key(|p| p.as_tuple());
+    for pos in chunks {
+        let saved = world.blocks.chunks[&pos].chunk.to_saved(world.blocks.registry);
+        // light depends on the order chunks were refreshed in, which may differ
+        hasher.write(&bincode::serialize(&(saved.pos, saved.state, saved.palette, saved.blocks, saved.block_entities)).unwrap_or_default());
+    }
+    let mut entities = world.entities.ecs.query::<(&NetId, &Position)>()
+        .iter()
+        .map(|(_, (id, pos))| (id.0, pos.pos.x.to_bits(), pos.pos.y.to_bits(), pos.pos.z.to_bits()))
+        .collect::<Vec<_>>();
+    entities.sort();
+    for e in entities {
+        hasher.write(&bincode::serialize(&e).unwrap_or_default());
+    }
+    hasher.write(&bincode::serialize(&world.rng).unwrap_or_default());
+    hasher.finish()
+}
+
+/// Writes everything a running server gets from outside to a replay file
+pub struct Recorder {
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+
+    /// Start recording a server whose world has not loaded anything yet
+    pub fn create(path: impl AsRef<Path>, server: &ServerLoop) -> io::Result<Self> {
+        let dir = match &server.world.io {
+            Some(io) => io.dir().to_owned(),
+            None => return Err(io::Error::new(io::ErrorKind::Other, "only worlds with storage can be recorded"))
+        };
+        let header = ReplayHeader {
+            tick_duration: server.tick_duration,
+            view_distance: server.view_distance,
+            max_chunks: server.max_chunks,
+            operators: server.operators.clone(),
+            world: Backup::of_dir(&dir)?,
+        };
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(REPLAY_MAGIC)?;
+        out.write_all(&PROTOCOL_VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut out, &header).map_err(invalid)?;
+        Ok(Self { out })
+    }
+
+    pub fn record(&mut self, tick: u64, event: &ReplayEvent) -> io::Result<()> {
+        bincode::serialize_into(&mut self.out, &(tick, event)).map_err(invalid)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+}
+
+/// Events of a replay file, handed out tick by tick
+pub struct Playback {
+    input: BufReader<File>,
+    next: Option<(u64, ReplayEvent)>,
+}
+
+impl Playback {
+
+    pub fn open(path: impl AsRef<Path>) -> io::Result<(Self, ReplayHeader)> {
+        let mut input = BufReader::new(File::open(path)?);
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic[..4] != REPLAY_MAGIC {
+            return Err(invalid("not a replay file"))
+        }
+        let version = u32::from_le_bytes([magic[4], magic[5], magic[6], magic[7]]);
+        if version != PROTOCOL_VERSION {
+            return Err(invalid(format!("replay has protocol version {}, this build has {}", version, PROTOCOL_VERSION)))
+        }
+        let header = bincode::deserialize_from(&mut input).map_err(invalid)?;
+        let mut playback = Self { input, next: None };
+        playback.advance();
+        Ok((playback, header))
+    }
+
+    fn advance(&mut self) {
+        // a recording cut short by a crash simply ends early
+        self.next = bincode::deserialize_from(&mut self.input).ok();
+    }
+
+    pub fn finished(&self) -> bool {
+        self.next.is_none()
+    }
+
+    /// Events recorded during `tick`, in order
+    pub fn events_at(&mut self, tick: u64) -> Vec<ReplayEvent> {
+        let mut events = vec![];
+        while let Some((t, _)) = &self.next {
+            if *t > tick {
+                break
+            }
+            let (_, event) = self.next.take().unwrap();
+            events.push(event);
+            self.advance();
+        }
+        events
+    }
+
+}
+
+/// Open the replay at `path` and restore the world it was recorded on as `world_name`, which must not exist yet
+pub fn restore_replay(path: impl AsRef<Path>, world_name: &str) -> io::Result<(Playback, ReplayHeader)> {
+    let (playback, header) = Playback::open(path)?;
+    let dir = crate::save::world_dir(world_name);
+    if dir.exists() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!(
+            "{} already exists, move or delete it to play back a replay", dir.display()
+        )))
+    }
+    header.world.restore(&dir)?;
+    Ok((playback, header))
+}
+
+/// Connection of a replayed client, everything sent to it is dropped
+pub struct NullConnection;
+
+impl Connection<ServerMsg, ClientMsg> for NullConnection {
+    fn send(&mut self, _msg: ServerMsg) -> io::Result<()> {
+        Ok(())
+    }
+    fn try_recv(&mut self) -> io::Result<Option<ClientMsg>> {
+        Ok(None)
+    }
+}
+
+impl<'cnt: 'b, 'b> ServerLoop<'cnt> {
+
+    /// Write `event` to the recording, if there is one
+    pub fn record(&mut self, event: &ReplayEvent) {
+        let tick = self.world.ticks;
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = recorder.record(tick, event) {
+                println!("Stopped recording: {}", e);
+                self.recorder = None;
+            }
+        }
+    }
+
+    /// End the recording with the current world checksum
+    pub fn stop_recording(&mut self) {
+        let checksum = world_checksum(&self.world);
+        self.record(&ReplayEvent::End(checksum));
+        if let Some(mut recorder) = self.recorder.take() {
+            match recorder.flush() {
+                Ok(()) => println!("Recording finished, world checksum {:016x}", checksum),
+                Err(e) => println!("Failed to finish recording: {}", e),
+            }
+        }
+    }
+
+    /// Load a server on the world restored by `restore_replay` to play it back.
+    /// `content` has to be finished with the block ids of the restored world.
+    pub fn open_replay(content: &'cnt Content, playback: Playback, header: &ReplayHeader, world_name: &str) -> Self {
+        let mut server = Self::new(content, world_name);
+        server.tick_duration = header.tick_duration;
+        server.view_distance = header.view_distance;
+        server.max_chunks = header.max_chunks;
+        server.operators = header.operators.clone();
+        server.playback = Some(playback);
+        server
+    }
+
+    /// Play back the next tick of the replay as fast as possible, returns false once the replay is over
+    pub fn replay_tick(&mut self) -> bool {
+        let tick = self.world.ticks + 1;
+        let playback = match &mut self.playback {
+            Some(playback) => playback,
+            None => return false
+        };
+        if playback.finished() {
+            println!("Replay ended at tick {} without a checksum, the recording was cut short", tick);
+            return false
+        }
+        let mut reads = HashSet::new();
+        let mut joins = vec![];
+        let mut end = None;
+        for event in playback.events_at(tick) {
+            match event {
+                ReplayEvent::ChunkRead(pos) => {
+                    reads.insert(pos);
+                }
+                ReplayEvent::Join(name) => {
+                    joins.push(name.clone());
+                    self.replayed.push(ReplayEvent::Join(name));
+                }
+                ReplayEvent::End(checksum) => end = Some(checksum),
+                event => self.replayed.push(event),
+            }
+        }
+        if !self.wait_for_reads(&reads, &joins) {
+            println!("Replay diverged at tick {}: chunks or players read in the recording never arrived", tick);
+            return false
+        }
+        self.world.allowed_chunks = Some(reads);
+        self.tick();
+
+        if let Some(expected) = end {
+            let checksum = world_checksum(&self.world);
+            if checksum == expected {
+                println!("Replay finished at tick {}, world matches the recording", tick);
+            } else {
+                println!("Replay finished at tick {}, world differs from the recording ({:016x} instead of {:016x})", tick, checksum, expected);
+            }
+            return false
+        }
+        true
+    }
+
+    /// Block until storage has read all of `chunks` and the data of the `players`, false if that takes too long
+    fn wait_for_reads(&mut self, chunks: &HashSet<ChunkPos>, players: &[String]) -> bool {
+        let start = Instant::now();
+        loop {
+            self.world.poll_io();
+            let missing = chunks.iter()
+                .filter(|p| !self.world.received_chunks.contains_key(p))
+                .copied()
+                .collect::<Vec<_>>();
+            let mut players_read = true;
+            for name in players {
+                players_read &= self.world.request_player(name);
+            }
+            if missing.is_empty() && players_read {
+                return true
+            }
+            if start.elapsed() > consts::REPLAY_READ_TIMEOUT {
+                return false
+            }
+            for pos in missing {
+                self.world.request_chunk(pos);
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+}
@@ -0,0 +1,85 @@
+use std::io::{self, Read, Write};
+use flate2::{Compression, read::DeflateDecoder, write::DeflateEncoder};
+use crate::save::SavedChunk;
+use crate::prelude::*;
+use super::*;
+
+/// Serialize and deflate a chunk for sending
+pub fn encode_chunk(saved: &SavedChunk) -> io::Result<Vec<u8>> {
+    let bytes = bincode::serialize(saved).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut encoder = DeflateEncoder::new(vec![], Compression::fast());
+    encoder.write_all(&bytes)?;
+    encoder.finish()
+}
+
+pub fn decode_chunk(bytes: &[u8]) -> io::Result<SavedChunk> {
+    let mut decoded = vec![];
+    DeflateDecoder::new(bytes).read_to_end(&mut decoded)?;
+    bincode::deserialize(&decoded).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Distance in chunks along the furthest axis
+fn chunk_distance(a: ChunkPos, b: ChunkPos) -> i32 {
+    let d = (a - b).0;
+    d.x.abs().max(d.y.abs()).max(d.z.abs())
+}
+
+impl<'cnt: 'b, 'b> ServerLoop<'cnt> {
+
+    /// Send each client the rendered chunks within the view distance it does not have yet, nearest first,
+    /// and tell it to unload the ones it moved away from
+    pub fn stream_chunks(&mut self) {
+        let vd = self.view_distance;
+        // clients close to each other mostly want the same chunks
+        let mut encoded: HashMap<ChunkPos, Vec<u8>> = HashMap::new();
+
+        for client in &mut self.clients {
+            let center = match client.chunk {
+                Some(center) => center,
+                None => continue
+            };
+
+            // one chunk of slack, so moving back and forth over a border does not resend chunks
+            let far = client.sent_chunks.iter()
+                .filter(|p| chunk_distance(**p, center) > vd + 1)
+                .copied()
+                .collect::<Vec<_>>();
+            for p in far {
+                client.sent_chunks.remove(&p);
+                client.send(ServerMsg::UnloadChunk(p));
+            }
+
+            let mut missing = vec![];
+            for dx in -vd..=vd {
+                for dy in -vd..=vd {
+                    for dz in -vd..=vd {
+                        let p = center + (dx, dy, dz).into();
+                        let rendered = self.world.blocks.chunks.get(&p).map_or(false, |c| c.chunk.renderable());
+                        if rendered && !client.sent_chunks.contains(&p) {
+                            missing.push((dx*dx + dy*dy + dz*dz, p));
+                        }
+                    }
+                }
+            }
+            missing.sort_by_key(|(d, _)| *d);
+            for (_, p) in missing.into_iter().take(consts::CHUNKS_SENT_PER_TICK) {
+                if !encoded.contains_key(&p) {
+                    let mut saved = self.world.blocks.chunks[&p].chunk.to_saved(self.world.blocks.registry);
+                    saved.state = ChunkState::Rendered;
+                    match encode_chunk(&saved) {
+                        Ok(bytes) => {
+                            encoded.insert(p, bytes);
+                        }
+                        Err(e) => {
+                            println!("Failed to encode chunk {:?}: {}", p, e);
+                            continue
+                        }
+                    }
+                }
+                client.sent_chunks.insert(p);
+                client.send(ServerMsg::ChunkData(p, encoded[&p].clone()));
+            }
+        }
+    }
+
+}
@@ -1,6 +1,8 @@
 
+#[cfg(feature = "client")]
 use crate::text::text::Text;
 pub use aabb_tree::Proxy;
+#[cfg(feature = "client")]
 use crate::TextureAtlas;
 use aabb_tree::AabbTree;
 use std::ffi::CString;
@@ -240,10 +242,12 @@ impl serde::Serialize for crate::Data {
     }
 } */
 
+#[cfg(feature = "client")]
 pub struct DebugText {
     pub text: Text,
 }
 
+#[cfg(feature = "client")]
 impl From<&Arc<Font>> for DebugText {
     fn from(f: &Arc<Font>) -> Self {
         Self {
@@ -252,6 +256,7 @@ impl From<&Arc<Font>> for DebugText {
     }
 }
 
+#[cfg(feature = "client")]
 impl DebugText {
     pub fn set_data(&mut self, pos: &WorldPos, looking_at: Option<(&String, BlockPos)>, delta: f32, last_tick_dur: f32) {
         self.text.set_text(
@@ -278,6 +283,7 @@ pub fn fdiv(x: i32, d: i32) -> i32 {
     (x as f32 / d as f32).floor() as i32
 }
 
+#[cfg(feature = "client")]
 pub fn gen_full_block_vao<'a>(b: impl std::iter::Iterator<Item = &'a Block>, m: &mut HashMap<String, i32>, a: &TextureAtlas) -> VAO {
 
     let xc = 0;
@@ -403,26 +409,31 @@ pub fn gen_full_block_vao<'a>(b: impl std::iter::Iterator<Item = &'a Block>, m:
     VAO::textured(&verts, &uvs)
 }
 
+#[cfg(feature = "client")]
 pub trait Drawable: Send + Sync {
     fn bind(&self);
     fn draw(&self);
 }
 
+#[cfg(feature = "client")]
 impl Drawable for Arc<VAO> {
     fn bind(&self) {self.as_ref().bind()}
     fn draw(&self) {self.as_ref().draw()}
 }
 
+#[cfg(feature = "client")]
 impl Drawable for VAO {
     fn bind(&self) {self.bind()}
     fn draw(&self) {self.draw()}
 }
 
+#[cfg(feature = "client")]
 pub struct RenderedItem {
     pub vao: Arc<VAO>,
     pub offset: i32,
 }
 
+#[cfg(feature = "client")]
 impl Drawable for RenderedItem {
     fn bind(&self) {self.vao.bind()}
     fn draw(&self) {self.vao.draw_n(6*6, self.offset)}
@@ -450,6 +461,37 @@ pub fn hash(h: &impl std::hash::Hash) -> u64 {
     std::hash::Hasher::finish(&dh)
 }
 
+/// FNV-1a hasher, which unlike `DefaultHasher` gives the same hashes with every toolchain and platform.
+/// Hashes are only stable when fed bytes or integers, not through `Hash` implementations.
+pub struct Fnv64(u64);
+
+impl Default for Fnv64 {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl std::hash::Hasher for Fnv64 {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 = (self.0 ^ *b as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Hash of `value` serialized with bincode, the same wherever it is computed
+pub fn stable_hash(value: &impl serde::Serialize) -> u64 {
+    let mut hasher = Fnv64::default();
+    std::hash::Hasher::write(&mut hasher, &bincode::serialize(value).unwrap_or_default());
+    std::hash::Hasher::finish(&hasher)
+}
+
 pub struct AsyncStdin {
     pub rx: std::sync::mpsc::Receiver<String>,
 }
@@ -462,7 +504,11 @@ impl AsyncStdin {
             let stdin = std::io::stdin();
             let mut lock = stdin.lock();
             loop {
-                if lock.read_line(&mut buf).is_err() {return}
+                buf.clear();
+                match lock.read_line(&mut buf) {
+                    Ok(0) | Err(_) => return,
+                    Ok(_) => {}
+                }
                 if tx.send(buf.clone()).is_err() {return}
             }
         });